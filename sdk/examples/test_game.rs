@@ -0,0 +1,126 @@
+//! `test_game` written against the SDK instead of the C headers.
+
+use console_sdk::{
+    dbg_println, metadata, sprite,
+    system::API_VERSION,
+    tile::{self, Color, Tile},
+    Cart, Position,
+};
+
+metadata! {
+    title = "Test Game",
+    api_version = 2,
+    gamepads = 1,
+}
+
+// The SDK has to have everything `api_version` above asks for
+const _: () = assert!(API_VERSION >= 2);
+
+const PALETTE_GROUND: [Color; 11] = [
+    Color(0x796543),
+    Color(0x816f48),
+    Color(0x897a4e),
+    Color(0x908353),
+    Color(0x998d58),
+    Color(0x008c32),
+    Color(0x159831),
+    Color(0x29a430),
+    Color(0x3eb02f),
+    Color(0x52bc2e),
+    Color(0x8080FF),
+];
+
+#[rustfmt::skip]
+const TILE_GRASS: Tile = Tile([
+    0x89, 0xA8, 0x9A, 0x8A, 0x98, 0xA9, 0x8A, 0xA9,
+    0x7A, 0x87, 0x89, 0x7A, 0x87, 0x98, 0xA8, 0x98,
+    0x78, 0x77, 0xA8, 0x78, 0x97, 0x89, 0x79, 0x87,
+    0x86, 0x79, 0x87, 0x98, 0x77, 0x87, 0x78, 0x67,
+    0x72, 0x78, 0x77, 0x86, 0x17, 0x62, 0x76, 0x27,
+    0x61, 0x27, 0x61, 0x72, 0x26, 0x12, 0x26, 0x12,
+    0x13, 0x16, 0x22, 0x61, 0x21, 0x23, 0x36, 0x12,
+    0x24, 0x32, 0x43, 0x12, 0x33, 0x44, 0x31, 0x21,
+    0x12, 0x23, 0x32, 0x14, 0x34, 0x53, 0x22, 0x13,
+    0x43, 0x21, 0x21, 0x23, 0x55, 0x44, 0x21, 0x33,
+    0x53, 0x42, 0x12, 0x45, 0x43, 0x32, 0x11, 0x44,
+    0x44, 0x33, 0x21, 0x34, 0x32, 0x12, 0x12, 0x35,
+    0x55, 0x32, 0x12, 0x23, 0x12, 0x42, 0x21, 0x43,
+    0x43, 0x42, 0x12, 0x12, 0x24, 0x33, 0x41, 0x23,
+    0x34, 0x31, 0x11, 0x21, 0x33, 0x44, 0x32, 0x13,
+    0x33, 0x21, 0x22, 0x12, 0x34, 0x55, 0x44, 0x12,
+]);
+
+#[rustfmt::skip]
+const TILE_DIRT: Tile = Tile([
+    0x21, 0x12, 0x24, 0x21, 0x25, 0x45, 0x43, 0x41,
+    0x11, 0x24, 0x33, 0x41, 0x13, 0x54, 0x54, 0x32,
+    0x23, 0x43, 0x44, 0x32, 0x13, 0x45, 0x43, 0x21,
+    0x34, 0x34, 0x54, 0x34, 0x21, 0x34, 0x32, 0x12,
+    0x44, 0x54, 0x54, 0x43, 0x12, 0x23, 0x41, 0x23,
+    0x34, 0x45, 0x43, 0x32, 0x11, 0x22, 0x12, 0x12,
+    0x33, 0x44, 0x34, 0x31, 0x24, 0x33, 0x21, 0x12,
+    0x24, 0x34, 0x43, 0x22, 0x33, 0x44, 0x31, 0x21,
+    0x12, 0x23, 0x32, 0x14, 0x34, 0x53, 0x22, 0x13,
+    0x43, 0x21, 0x21, 0x23, 0x55, 0x44, 0x21, 0x33,
+    0x53, 0x42, 0x12, 0x45, 0x43, 0x32, 0x11, 0x44,
+    0x44, 0x33, 0x21, 0x34, 0x32, 0x12, 0x12, 0x35,
+    0x55, 0x32, 0x12, 0x23, 0x12, 0x42, 0x21, 0x43,
+    0x43, 0x42, 0x12, 0x12, 0x24, 0x33, 0x41, 0x23,
+    0x34, 0x31, 0x11, 0x21, 0x33, 0x44, 0x32, 0x13,
+    0x33, 0x21, 0x22, 0x12, 0x34, 0x55, 0x44, 0x12,
+]);
+
+struct Player {
+    position: Position,
+    velocity: Position,
+}
+
+struct TestGame {
+    player: Player,
+}
+
+impl Player {
+    fn update(&mut self) {
+        self.position.x += self.velocity.x;
+        self.position.y += self.velocity.y;
+        self.velocity.y += 1;
+        self.position.x = self.position.x.rem_euclid(320);
+        self.position.y = self.position.y.rem_euclid(240);
+
+        sprite::set_position(0, self.position);
+    }
+}
+
+impl Cart for TestGame {
+    fn init() -> Self {
+        dbg_println!("Hello, world!");
+        for (i, color) in PALETTE_GROUND.into_iter().enumerate() {
+            tile::write_palette(i as u8, color);
+        }
+        tile::write_tile(1, &TILE_GRASS);
+        tile::write_tile(2, &TILE_DIRT);
+
+        tile::set_background_palette(10);
+
+        for x in 0..20 {
+            tile::set_background_tile(0, Position::new(x, 13), 1);
+            tile::set_background_tile(0, Position::new(x, 14), 2);
+        }
+
+        sprite::set_visible(0, true);
+        sprite::set_tile(0, 2);
+
+        Self {
+            player: Player {
+                position: Position::new(30, 31),
+                velocity: Position::new(5, 1),
+            },
+        }
+    }
+
+    fn update(&mut self) {
+        self.player.update();
+    }
+}
+
+console_sdk::cart!(TestGame);
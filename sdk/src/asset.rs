@@ -0,0 +1,19 @@
+//! Assets are named blobs of data packaged with the cart.
+
+use core::ffi::CStr;
+
+use crate::sys;
+
+/// The size of an asset, or `None` if there isn't one called `name`
+pub fn size(name: &CStr) -> Option<u32> {
+    let size = unsafe { sys::asset::size(name.as_ptr()) };
+    u32::try_from(size).ok()
+}
+
+/// Copies as much of the asset as fits in `data`, starting at `offset`, returning how many
+/// bytes were copied.
+pub fn read(name: &CStr, offset: u32, data: &mut [u8]) -> usize {
+    unsafe {
+        sys::asset::read(name.as_ptr(), offset, data.as_mut_ptr(), data.len() as u32) as usize
+    }
+}
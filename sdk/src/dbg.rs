@@ -0,0 +1,50 @@
+use core::{ffi::CStr, fmt};
+
+use crate::sys;
+
+/// Writes to the debug console, for use with `write!`.
+pub struct Writer;
+
+impl fmt::Write for Writer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_str(s);
+        Ok(())
+    }
+}
+
+pub fn write_char(c: char) {
+    unsafe { sys::dbg::write_character(c as u32) }
+}
+
+pub fn write_str(s: &str) {
+    s.chars().for_each(write_char);
+}
+
+pub fn write_c_str(s: &CStr) {
+    unsafe { sys::dbg::write_str(s.as_ptr()) }
+}
+
+pub fn write_int(i: i64) {
+    unsafe { sys::dbg::write_int(i) }
+}
+
+pub fn write_uint(i: u64) {
+    unsafe { sys::dbg::write_uint(i) }
+}
+
+pub fn write_ptr<T>(p: *const T) {
+    unsafe { sys::dbg::write_ptr(p.cast()) }
+}
+
+pub fn end_line() {
+    unsafe { sys::dbg::end_line() }
+}
+
+/// Like `println!`, but to the debug console
+#[macro_export]
+macro_rules! dbg_println {
+    ($($arg: tt)*) => {{
+        let _ = core::fmt::Write::write_fmt(&mut $crate::dbg::Writer, format_args!($($arg)*));
+        $crate::dbg::end_line();
+    }};
+}
@@ -0,0 +1,27 @@
+use bitflags::bitflags;
+
+use crate::sys;
+
+bitflags! {
+    /// The same layout as the console's gamepad state
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct GamepadState: u16 {
+        const Up = 1;
+        const Down = 2;
+        const Left = 4;
+        const Right = 8;
+        const North = 16;
+        const South = 32;
+        const East = 64;
+        const West = 128;
+        const LeftBumper = 256;
+        const RightBumper = 512;
+        const Start = 1024;
+        const Select = 2048;
+        const Disconnected = 4096;
+    }
+}
+
+pub fn get_state(gamepad: u8) -> GamepadState {
+    GamepadState::from_bits_retain(unsafe { sys::gamepad::get_state(gamepad as u32) } as u16)
+}
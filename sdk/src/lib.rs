@@ -0,0 +1,181 @@
+//! Safe bindings for writing carts in Rust, the equivalent of the C headers in `std/`.
+//!
+//! A cart implements [`Cart`] and exports it with [`cart!`]:
+//!
+//! ```ignore
+//! struct Game;
+//!
+//! impl console_sdk::Cart for Game {
+//!     fn init() -> Self {
+//!         Game
+//!     }
+//!
+//!     fn update(&mut self) {}
+//! }
+//!
+//! console_sdk::cart!(Game);
+//! ```
+//!
+//! Only `init` and `update` are exported unless the cart lists its other hooks, like
+//! `cart!(Game, draw, on_pause)`.
+#![no_std]
+
+use core::cell::UnsafeCell;
+
+pub mod asset;
+pub mod dbg;
+pub mod gamepad;
+pub mod math;
+pub mod save;
+pub mod sprite;
+pub mod sys;
+pub mod system;
+pub mod tile;
+
+pub use gamepad::GamepadState;
+pub use math::Fixed;
+
+/// A position on the screen or within a background
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Position {
+    pub x: i16,
+    pub y: i16,
+}
+
+impl Position {
+    pub const fn new(x: i16, y: i16) -> Self {
+        Self { x, y }
+    }
+}
+
+/// A cart's state, and how it reacts to the console. Only `init` and `update` are required, the
+/// other hooks are only called when they're listed in [`cart!`].
+pub trait Cart: Sized {
+    /// Called once when the cart starts, or after it's reset
+    fn init() -> Self;
+
+    /// Called at the start of every frame
+    fn update(&mut self);
+
+    /// Called after `update`, before the scanlines are drawn
+    fn draw(&mut self) {}
+
+    /// Called before each scanline is drawn
+    fn v_blank(&mut self, _y: u32) {}
+
+    fn on_pause(&mut self) {}
+
+    fn on_resume(&mut self) {}
+
+    /// Called before the console resets the cart
+    fn on_reset(&mut self) {}
+
+    /// Called before the console exits, a good time to write save data
+    fn on_exit(&mut self) {}
+}
+
+/// Exports the lifecycle functions for a type implementing [`Cart`]: `init`, `update`, and any
+/// of `draw`, `v_blank`, `on_pause`, `on_resume`, `on_reset` and `on_exit` listed after it.
+#[macro_export]
+macro_rules! cart {
+    (@hook v_blank) => {
+        #[no_mangle]
+        pub extern "C" fn v_blank(y: u32) {
+            // SAFETY: the console calls into the cart one function at a time
+            $crate::Cart::v_blank(unsafe { CART.get() }, y);
+        }
+    };
+    (@hook $hook: ident) => {
+        #[no_mangle]
+        pub extern "C" fn $hook() {
+            // SAFETY: the console calls into the cart one function at a time
+            $crate::Cart::$hook(unsafe { CART.get() });
+        }
+    };
+    ($cart: ty $(, $hook: ident)* $(,)?) => {
+        static CART: $crate::CartCell<$cart> = $crate::CartCell::new();
+
+        #[no_mangle]
+        pub extern "C" fn init() {
+            // SAFETY: the console calls into the cart one function at a time
+            unsafe { CART.set(<$cart as $crate::Cart>::init()) };
+        }
+
+        #[no_mangle]
+        pub extern "C" fn update() {
+            // SAFETY: the console calls into the cart one function at a time
+            $crate::Cart::update(unsafe { CART.get() });
+        }
+
+        $($crate::cart!(@hook $hook);)*
+    };
+}
+
+/// Puts `key = "value"` pairs in the cart's `cart_metadata` custom section.
+///
+/// ```ignore
+/// console_sdk::metadata! {
+///     title = "Test Game",
+///     api_version = 2,
+/// }
+/// ```
+#[macro_export]
+macro_rules! metadata {
+    ($($key: ident = $value: literal),* $(,)?) => {
+        const _: () = {
+            const TEXT: &str = concat!($(stringify!($key), "=", $value, "\n"),*);
+
+            #[link_section = "cart_metadata"]
+            #[used]
+            static METADATA: [u8; TEXT.len()] = $crate::to_array(TEXT);
+        };
+    };
+}
+
+/// Where [`cart!`] keeps the cart's state.
+#[doc(hidden)]
+pub struct CartCell<T>(UnsafeCell<Option<T>>);
+
+unsafe impl<T> Sync for CartCell<T> {}
+
+impl<T> Default for CartCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> CartCell<T> {
+    pub const fn new() -> Self {
+        Self(UnsafeCell::new(None))
+    }
+
+    /// # Safety
+    ///
+    /// No reference from [`CartCell::get`] can be alive. That holds in the functions [`cart!`]
+    /// exports, carts are single threaded and the console never calls into one that's already
+    /// running.
+    pub unsafe fn set(&self, cart: T) {
+        unsafe { *self.0.get() = Some(cart) }
+    }
+
+    /// # Safety
+    ///
+    /// No other reference from here can be alive while the one returned is, like
+    /// [`CartCell::set`].
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get(&self) -> &mut T {
+        unsafe { (*self.0.get()).as_mut() }.expect("the console calls `init` first")
+    }
+}
+
+#[doc(hidden)]
+pub const fn to_array<const N: usize>(text: &str) -> [u8; N] {
+    let bytes = text.as_bytes();
+    let mut out = [0; N];
+    let mut i = 0;
+    while i < N {
+        out[i] = bytes[i];
+        i += 1;
+    }
+    out
+}
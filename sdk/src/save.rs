@@ -0,0 +1,16 @@
+//! Save data persists between runs of the cart.
+
+use crate::sys;
+
+/// How many bytes of save data there are
+pub fn size() -> u32 {
+    unsafe { sys::save::size() }
+}
+
+pub fn read(offset: u32, data: &mut [u8]) {
+    unsafe { sys::save::read(offset, data.as_mut_ptr(), data.len() as u32) }
+}
+
+pub fn write(offset: u32, data: &[u8]) {
+    unsafe { sys::save::write(offset, data.as_ptr(), data.len() as u32) }
+}
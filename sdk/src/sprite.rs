@@ -0,0 +1,71 @@
+use crate::{
+    sys,
+    tile::{BlendMode, Matrix},
+    Position,
+};
+
+pub fn set_visible(sprite: u8, visible: bool) {
+    unsafe { sys::sprite::set_sprite_visible(sprite as u32, visible as u32) }
+}
+
+pub fn set_tile(sprite: u8, tile: u8) {
+    unsafe { sys::sprite::set_sprite_tile(sprite as u32, tile as u32) }
+}
+
+pub fn set_palette(sprite: u8, palette: u8) {
+    unsafe { sys::sprite::set_sprite_palette(sprite as u32, palette as u32) }
+}
+
+pub fn get_position(sprite: u8) -> Position {
+    unsafe {
+        Position::new(
+            sys::sprite::get_sprite_position_x(sprite as u32) as i16,
+            sys::sprite::get_sprite_position_y(sprite as u32) as i16,
+        )
+    }
+}
+
+pub fn set_position(sprite: u8, pos: Position) {
+    unsafe { sys::sprite::set_sprite_position(sprite as u32, pos.x as i32, pos.y as i32) }
+}
+
+pub fn set_flip(sprite: u8, flip_x: bool, flip_y: bool) {
+    unsafe { sys::sprite::set_sprite_flip(sprite as u32, flip_x as u32, flip_y as u32) }
+}
+
+/// Puts the sprite behind the first `priority` backgrounds, 0 is in front of all of them.
+pub fn set_priority(sprite: u8, priority: u8) {
+    unsafe { sys::sprite::set_sprite_priority(sprite as u32, priority as u32) }
+}
+
+/// Sets the sprite's size in pixels, each side is 8, 16, 32 or 64. Sprites bigger than a tile are
+/// made of consecutive tiles from the sprite's tile, a row at a time.
+pub fn set_size(sprite: u8, width: u8, height: u8) {
+    unsafe { sys::sprite::set_sprite_size(sprite as u32, width as u32, height as u32) }
+}
+
+/// Sets one of the matrices affine sprites share. It transforms the screen into the sprite, around
+/// the sprite's center.
+pub fn write_matrix(matrix: u8, value: Matrix) {
+    unsafe {
+        sys::sprite::write_sprite_matrix(
+            matrix as u32,
+            value.a.raw() as i32,
+            value.b.raw() as i32,
+            value.c.raw() as i32,
+            value.d.raw() as i32,
+        )
+    }
+}
+
+/// Rotates and scales the sprite with a shared matrix, or draws it normally with `None`. Double
+/// size sprites are drawn in a box twice as big, so they aren't cut off.
+pub fn set_matrix(sprite: u8, matrix: Option<u8>, double_size: bool) {
+    let matrix = matrix.map_or(-1, |matrix| matrix as i32);
+    unsafe { sys::sprite::set_sprite_matrix(sprite as u32, matrix, double_size as u32) }
+}
+
+/// Sets how the sprite's pixels blend with what's under them.
+pub fn set_blend(sprite: u8, mode: BlendMode) {
+    unsafe { sys::sprite::set_sprite_blend(sprite as u32, mode as u32) }
+}
@@ -0,0 +1,115 @@
+//! The raw functions the console gives carts, see the other modules for safe wrappers.
+//!
+//! Off the console these panic, so the SDK and carts still build for the host.
+//!
+//! Generated from the host API in src/wasm.rs by `abi write`, don't edit it by hand.
+//! Functions returning a position aren't here, use the ones for x and y instead.
+
+use core::ffi::c_char;
+
+macro_rules! imports {
+    ($(mod $module: ident = $name: literal {
+        $(fn $f: ident($($arg: ident: $ty: ty),*) $(-> $ret: ty)?;)*
+    })*) => {
+        $(
+            #[allow(clippy::missing_safety_doc)]
+            pub mod $module {
+                #[allow(unused_imports)]
+                use super::*;
+
+                #[cfg(target_arch = "wasm32")]
+                #[link(wasm_import_module = $name)]
+                extern "C" {
+                    $(pub fn $f($($arg: $ty),*) $(-> $ret)?;)*
+                }
+
+                $(
+                    #[cfg(not(target_arch = "wasm32"))]
+                    #[allow(unused_variables)]
+                    pub unsafe fn $f($($arg: $ty),*) $(-> $ret)? {
+                        panic!(concat!("`", $name, "::", stringify!($f), "` only exists on the console"))
+                    }
+                )*
+            }
+        )*
+    };
+}
+
+imports! {
+    mod tile = "tile" {
+        fn write_palette(palette: u32, color: u32);
+        fn write_tile(tile_number: u32, tile: *const u8);
+        fn set_background_tile(background: u32, x: i32, y: i32, tile: u32);
+        fn set_background_visible(background: u32, visible: u32);
+        fn set_background_blend(background: u32, mode: u32);
+        fn set_blend_targets(targets: u32);
+        fn set_blend_alpha(top: u32, below: u32);
+        fn set_background_palette(palette: u32);
+        fn set_background_tile_palette(background: u32, x: i32, y: i32, palette: u32);
+        fn set_background_tile_flip(background: u32, x: i32, y: i32, flip_x: u32, flip_y: u32);
+        fn set_background_tile_priority(background: u32, x: i32, y: i32, above_sprites: u32);
+        fn set_background_scroll_pre(background: u32, x: i32, y: i32);
+        fn set_background_scroll_pre_x(background: u32, x: i32);
+        fn set_background_scroll_pre_y(background: u32, y: i32);
+        fn get_background_scroll_pre_x(background: u32) -> i32;
+        fn get_background_scroll_pre_y(background: u32) -> i32;
+        fn set_background_scroll_post(background: u32, x: i32, y: i32);
+        fn set_background_scroll_post_x(background: u32, x: i32);
+        fn set_background_scroll_post_y(background: u32, y: i32);
+        fn get_background_scroll_post_x(background: u32) -> i32;
+        fn get_background_scroll_post_y(background: u32) -> i32;
+        fn set_background_transformation_matrix(background: u32, a: i32, b: i32, c: i32, d: i32);
+    }
+
+    mod sprite = "sprite" {
+        fn set_sprite_tile(sprite: u32, tile: u32);
+        fn set_sprite_visible(sprite: u32, visible: u32);
+        fn set_sprite_palette(sprite: u32, palette: u32);
+        fn get_sprite_position_x(sprite: u32) -> i32;
+        fn get_sprite_position_y(sprite: u32) -> i32;
+        fn set_sprite_position(sprite: u32, x: i32, y: i32);
+        fn set_sprite_flip(sprite: u32, flip_x: u32, flip_y: u32);
+        fn set_sprite_priority(sprite: u32, priority: u32);
+        fn set_sprite_size(sprite: u32, width: u32, height: u32);
+        fn write_sprite_matrix(matrix: u32, a: i32, b: i32, c: i32, d: i32);
+        fn set_sprite_matrix(sprite: u32, matrix: i32, double_size: u32);
+        fn set_sprite_blend(sprite: u32, mode: u32);
+    }
+
+    mod dbg = "dbg" {
+        fn write_character(c: u32);
+        fn end_line();
+        fn write_str(s: *const c_char);
+        fn write_int(i: i64);
+        fn write_uint(i: u64);
+        fn write_ptr(p: *const u8);
+    }
+
+    mod gamepad = "gamepad" {
+        fn get_state(gamepad: u32) -> u32;
+    }
+
+    mod save = "save" {
+        fn size() -> u32;
+        fn read(offset: u32, data: *mut u8, len: u32);
+        fn write(offset: u32, data: *const u8, len: u32);
+    }
+
+    mod asset = "asset" {
+        fn size(name: *const c_char) -> i32;
+        fn read(name: *const c_char, offset: u32, data: *mut u8, len: u32) -> u32;
+    }
+
+    mod system = "system" {
+        fn api_version() -> u32;
+        fn features() -> u32;
+        fn screen_width() -> u32;
+        fn screen_height() -> u32;
+        fn sprite_count() -> u32;
+        fn background_count() -> u32;
+        fn tile_count() -> u32;
+        fn palette_size() -> u32;
+        fn gamepad_count() -> u32;
+        fn sprite_matrix_count() -> u32;
+    }
+}
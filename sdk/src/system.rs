@@ -0,0 +1,56 @@
+//! What the console the cart's running on supports.
+
+use bitflags::bitflags;
+
+use crate::sys;
+
+/// The API version this SDK is for, put it in the cart's metadata as `api_version`
+pub const API_VERSION: u32 = 7;
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Features: u32 {
+        const SAVE_DATA = 1;
+        const ASSETS = 2;
+        const HOOKS = 4;
+        const WASI = 8;
+    }
+}
+
+pub fn api_version() -> u32 {
+    unsafe { sys::system::api_version() }
+}
+
+pub fn features() -> Features {
+    Features::from_bits_retain(unsafe { sys::system::features() })
+}
+
+/// The screen's width and height in pixels
+pub fn screen_size() -> (u32, u32) {
+    unsafe { (sys::system::screen_width(), sys::system::screen_height()) }
+}
+
+pub fn sprite_count() -> u32 {
+    unsafe { sys::system::sprite_count() }
+}
+
+pub fn background_count() -> u32 {
+    unsafe { sys::system::background_count() }
+}
+
+pub fn tile_count() -> u32 {
+    unsafe { sys::system::tile_count() }
+}
+
+pub fn palette_size() -> u32 {
+    unsafe { sys::system::palette_size() }
+}
+
+pub fn gamepad_count() -> u32 {
+    unsafe { sys::system::gamepad_count() }
+}
+
+/// How many matrices affine sprites can share
+pub fn sprite_matrix_count() -> u32 {
+    unsafe { sys::system::sprite_matrix_count() }
+}
@@ -0,0 +1,184 @@
+use bitflags::bitflags;
+
+use crate::{sys, Fixed, Position};
+
+/// 16x16 pixels, each a nibble indexing into the palette, where 0 is transparent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile(pub [u8; 128]);
+
+/// A color as 0xRRGGBB
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Color(pub u32);
+
+impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self(((r as u32) << 16) | ((g as u32) << 8) | b as u32)
+    }
+}
+
+/// How a background or sprite's pixels blend with what's under them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum BlendMode {
+    #[default]
+    None,
+    /// Mixed by the coefficients from [`set_blend_alpha`]
+    Alpha,
+    Add,
+    /// What's under it subtracted from it
+    Subtract,
+    /// The average of the two
+    Half,
+}
+
+bitflags! {
+    /// The layers blending pixels blend with, see [`set_blend_targets`]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct BlendTargets: u32 {
+        const BACKGROUND_0 = 1;
+        const BACKGROUND_1 = 2;
+        const BACKGROUND_2 = 4;
+        const BACKGROUND_3 = 8;
+        const BACKGROUND_4 = 16;
+        const BACKGROUND_5 = 32;
+        const BACKGROUND_6 = 64;
+        const BACKGROUND_7 = 128;
+        const SPRITES = 256;
+        /// The background color
+        const BACKDROP = 512;
+    }
+}
+
+/// A 2x2 matrix transforming the screen into a background or an affine sprite, each entry in 8.8
+/// fixed point
+#[derive(Debug, Clone, Copy)]
+pub struct Matrix {
+    pub a: Fixed,
+    pub b: Fixed,
+    pub c: Fixed,
+    pub d: Fixed,
+}
+
+impl Matrix {
+    pub const IDENTITY: Self = Self {
+        a: Fixed::ONE,
+        b: Fixed::ZERO,
+        c: Fixed::ZERO,
+        d: Fixed::ONE,
+    };
+}
+
+pub fn write_palette(palette: u8, color: Color) {
+    unsafe { sys::tile::write_palette(palette as u32, color.0) }
+}
+
+pub fn write_tile(tile: u8, data: &Tile) {
+    unsafe { sys::tile::write_tile(tile as u32, data.0.as_ptr()) }
+}
+
+/// Sets the palette entry drawn where there's nothing else.
+pub fn set_background_palette(palette: u8) {
+    unsafe { sys::tile::set_background_palette(palette as u32) }
+}
+
+/// Sets the tile at a position in a background, which wraps around the 64x64 map.
+pub fn set_background_tile(bg: u8, pos: Position, tile: u8) {
+    unsafe { sys::tile::set_background_tile(bg as u32, pos.x as i32, pos.y as i32, tile as u32) }
+}
+
+/// Sets the palette offset of the tile at a position in a background.
+pub fn set_background_tile_palette(bg: u8, pos: Position, palette: u8) {
+    unsafe {
+        sys::tile::set_background_tile_palette(
+            bg as u32,
+            pos.x as i32,
+            pos.y as i32,
+            palette as u32,
+        )
+    }
+}
+
+/// Mirrors the tile at a position in a background.
+pub fn set_background_tile_flip(bg: u8, pos: Position, flip_x: bool, flip_y: bool) {
+    unsafe {
+        sys::tile::set_background_tile_flip(
+            bg as u32,
+            pos.x as i32,
+            pos.y as i32,
+            flip_x as u32,
+            flip_y as u32,
+        )
+    }
+}
+
+/// Draws the tile at a position in a background over sprites, whatever their priority.
+pub fn set_background_tile_priority(bg: u8, pos: Position, above_sprites: bool) {
+    unsafe {
+        sys::tile::set_background_tile_priority(
+            bg as u32,
+            pos.x as i32,
+            pos.y as i32,
+            above_sprites as u32,
+        )
+    }
+}
+
+/// Sets how a background's pixels blend with what's under them.
+pub fn set_background_blend(bg: u8, mode: BlendMode) {
+    unsafe { sys::tile::set_background_blend(bg as u32, mode as u32) }
+}
+
+/// Sets which layers blending pixels blend with, pixels over any other layer are drawn as they
+/// are. All of them to start with.
+pub fn set_blend_targets(targets: BlendTargets) {
+    unsafe { sys::tile::set_blend_targets(targets.bits()) }
+}
+
+/// Sets how much of a pixel and what's under it [`BlendMode::Alpha`] takes, each out of 16.
+pub fn set_blend_alpha(top: u8, below: u8) {
+    unsafe { sys::tile::set_blend_alpha(top as u32, below as u32) }
+}
+
+pub fn set_background_visible(bg: u8, visible: bool) {
+    unsafe { sys::tile::set_background_visible(bg as u32, visible as u32) }
+}
+
+/// Scrolls a background before it's transformed by its matrix.
+pub fn set_background_scroll_pre(bg: u8, scroll: Position) {
+    unsafe { sys::tile::set_background_scroll_pre(bg as u32, scroll.x as i32, scroll.y as i32) }
+}
+
+pub fn get_background_scroll_pre(bg: u8) -> Position {
+    unsafe {
+        Position::new(
+            sys::tile::get_background_scroll_pre_x(bg as u32) as i16,
+            sys::tile::get_background_scroll_pre_y(bg as u32) as i16,
+        )
+    }
+}
+
+/// Scrolls a background after it's transformed by its matrix.
+pub fn set_background_scroll_post(bg: u8, scroll: Position) {
+    unsafe { sys::tile::set_background_scroll_post(bg as u32, scroll.x as i32, scroll.y as i32) }
+}
+
+pub fn get_background_scroll_post(bg: u8) -> Position {
+    unsafe {
+        Position::new(
+            sys::tile::get_background_scroll_post_x(bg as u32) as i16,
+            sys::tile::get_background_scroll_post_y(bg as u32) as i16,
+        )
+    }
+}
+
+pub fn set_background_transformation_matrix(bg: u8, matrix: Matrix) {
+    unsafe {
+        sys::tile::set_background_transformation_matrix(
+            bg as u32,
+            matrix.a.raw() as i32,
+            matrix.b.raw() as i32,
+            matrix.c.raw() as i32,
+            matrix.d.raw() as i32,
+        )
+    }
+}
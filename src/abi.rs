@@ -0,0 +1,404 @@
+use std::{
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    gamepad::GamepadState,
+    system::{Features, API_VERSION},
+    tile::{BlendMode, BlendTargets},
+};
+
+/// A type in the host API, as the cart sees it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbiType {
+    U8,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    Bool,
+    Char,
+    /// A pointer to memory the host writes to
+    Ptr,
+    /// A pointer to memory the host reads
+    ConstPtr,
+    /// A pointer to bytes the host reads
+    ConstBytes,
+    /// A pointer to a nul terminated string
+    Str,
+    /// An x and y, passed as two values
+    Position,
+    /// A position in a background's map, passed as two values
+    MapPosition,
+    GamepadState,
+    BlendMode,
+}
+
+/// An import module the console gives carts
+#[derive(Debug)]
+pub struct AbiModule {
+    pub name: &'static str,
+    pub doc: &'static [&'static str],
+    pub functions: &'static [AbiFunction],
+}
+
+#[derive(Debug)]
+pub struct AbiFunction {
+    pub name: &'static str,
+    /// What the function's called in the C headers
+    pub c_name: &'static str,
+    pub doc: &'static [&'static str],
+    pub params: &'static [AbiParam],
+    /// Empty, or the one type it returns
+    pub results: &'static [AbiType],
+}
+
+#[derive(Debug)]
+pub struct AbiParam {
+    pub name: &'static str,
+    pub ty: AbiType,
+}
+
+impl AbiType {
+    pub fn c_type(self) -> &'static str {
+        match self {
+            Self::U8 => "uint8_t",
+            Self::I16 => "int16_t",
+            Self::U32 => "uint32_t",
+            Self::I32 => "int32_t",
+            Self::U64 => "uint64_t",
+            Self::I64 => "int64_t",
+            Self::Bool => "bool",
+            Self::Char => "char",
+            Self::Ptr => "void *",
+            Self::ConstPtr => "const void *",
+            Self::ConstBytes => "const uint8_t *",
+            Self::Str => "const char *",
+            Self::Position => "position_s16_t",
+            Self::MapPosition => "position_u8_t",
+            Self::GamepadState => "gamepadstate_t",
+            Self::BlendMode => "blendmode_t",
+        }
+    }
+
+    /// The types of the wasm values it's passed as
+    pub fn wasm_types(self) -> &'static [&'static str] {
+        match self {
+            Self::U64 | Self::I64 => &["i64"],
+            Self::Position | Self::MapPosition => &["i32", "i32"],
+            _ => &["i32"],
+        }
+    }
+
+    /// The type in the Rust SDK's raw imports
+    fn rust_type(self) -> &'static str {
+        match self {
+            Self::I16 | Self::I32 | Self::Position | Self::MapPosition => "i32",
+            Self::U64 => "u64",
+            Self::I64 => "i64",
+            Self::Ptr => "*mut u8",
+            Self::ConstPtr | Self::ConstBytes => "*const u8",
+            Self::Str => "*const c_char",
+            _ => "u32",
+        }
+    }
+
+    /// Rust can't return two values from an import, and passes positions as an `x` and `y`
+    fn rust_params(self, name: &str) -> Vec<String> {
+        match self {
+            Self::Position | Self::MapPosition => {
+                vec![
+                    format!("x: {}", self.rust_type()),
+                    format!("y: {}", self.rust_type()),
+                ]
+            }
+            _ => vec![format!("{name}: {}", self.rust_type())],
+        }
+    }
+}
+
+impl AbiFunction {
+    /// The types of the wasm values the function takes
+    pub fn wasm_params(&self) -> Vec<&'static str> {
+        self.params
+            .iter()
+            .flat_map(|param| param.ty.wasm_types())
+            .copied()
+            .collect()
+    }
+
+    pub fn wasm_results(&self) -> Vec<&'static str> {
+        self.results
+            .iter()
+            .flat_map(|ty| ty.wasm_types())
+            .copied()
+            .collect()
+    }
+}
+
+/// The C header for a module, which lives at `std/<module>.h`.
+pub fn c_header(module: &AbiModule) -> String {
+    let mut out = String::new();
+    out += "// Generated from the host API in src/wasm.rs by `abi write`, don't edit it by hand.\n";
+    out += "#pragma once\n\n";
+    out += "#include <common_types.h>\n#include <defines.h>\n#include <stdbool.h>\n#include <stdint.h>\n";
+    // For `blendmode_t`
+    if module.name == "sprite" {
+        out += "#include <tile.h>\n";
+    }
+    out += "\n";
+    out += "#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n";
+
+    for line in module.doc {
+        writeln!(out, "//{line}").unwrap();
+    }
+    if !module.doc.is_empty() {
+        out += "\n";
+    }
+
+    out += &c_header_extras(module.name);
+
+    for function in module.functions {
+        for line in function.doc {
+            writeln!(out, "//{line}").unwrap();
+        }
+        let ret = function.results.first().map_or("void", |ty| ty.c_type());
+        let params: Vec<String> = function
+            .params
+            .iter()
+            .map(|param| format!("{} {}", param.ty.c_type(), param.name).replace("* ", "*"))
+            .collect();
+        writeln!(out, "WASM(\"{}\", \"{}\")", module.name, function.name).unwrap();
+        writeln!(
+            out,
+            "extern {ret} {}({});\n",
+            function.c_name,
+            params.join(", ")
+        )
+        .unwrap();
+    }
+
+    out += "#ifdef __cplusplus\n}\n#endif\n";
+    out
+}
+
+/// Types and constants that go along with a module, also generated from the host's source.
+fn c_header_extras(module: &str) -> String {
+    let mut out = String::new();
+    match module {
+        "gamepad" => {
+            out += "typedef enum {\n";
+            let flags: Vec<String> = GamepadState::all()
+                .iter_names()
+                .map(|(name, flag)| format!("    Gamepad_{name} = {}", flag.bits()))
+                .collect();
+            out += &flags.join(",\n");
+            out += "\n} gamepadstate_t;\n\n";
+        }
+        "tile" => {
+            out += "typedef enum {\n";
+            let modes: Vec<String> = BlendMode::ALL
+                .iter()
+                .map(|&mode| format!("    Blend_{mode:?} = {}", mode as u8))
+                .collect();
+            out += &modes.join(",\n");
+            out += "\n} blendmode_t;\n\n";
+            for (name, flag) in BlendTargets::all().iter_names() {
+                writeln!(out, "#define BLEND_TARGET_{name} {}", flag.bits()).unwrap();
+            }
+            out += "\n";
+        }
+        "system" => {
+            out += "// The API version these headers are for, put it in the cart's metadata as api_version\n";
+            writeln!(out, "#define API_VERSION {API_VERSION}\n").unwrap();
+            for (name, flag) in Features::all().iter_names() {
+                writeln!(out, "#define FEATURE_{name} {}", flag.bits()).unwrap();
+            }
+            out += "\n";
+        }
+        _ => {}
+    }
+    out
+}
+
+/// The Rust SDK's raw imports, which live at `sdk/src/sys.rs`.
+pub fn rust_sys(modules: &[AbiModule]) -> String {
+    let mut out = String::new();
+    out +=
+        "//! The raw functions the console gives carts, see the other modules for safe wrappers.\n";
+    out += "//!\n";
+    out += "//! Off the console these panic, so the SDK and carts still build for the host.\n";
+    out += "//!\n";
+    out +=
+        "//! Generated from the host API in src/wasm.rs by `abi write`, don't edit it by hand.\n";
+    out += "//! Functions returning a position aren't here, use the ones for x and y instead.\n\n";
+    out += RUST_IMPORTS_MACRO;
+    out += "\nimports! {\n";
+
+    for (i, module) in modules.iter().enumerate() {
+        if i > 0 {
+            out += "\n";
+        }
+        writeln!(out, "    mod {0} = \"{0}\" {{", module.name).unwrap();
+        for function in module.functions {
+            let ret = match function.results.first() {
+                Some(AbiType::Position | AbiType::MapPosition) => continue,
+                Some(ty) => format!(" -> {}", ty.rust_type()),
+                None => String::new(),
+            };
+            let params: Vec<String> = function
+                .params
+                .iter()
+                .flat_map(|param| param.ty.rust_params(param.name))
+                .collect();
+            writeln!(
+                out,
+                "        fn {}({}){ret};",
+                function.name,
+                params.join(", ")
+            )
+            .unwrap();
+        }
+        out += "    }\n";
+    }
+
+    out += "}\n";
+    out
+}
+
+const RUST_IMPORTS_MACRO: &str = r#"use core::ffi::c_char;
+
+macro_rules! imports {
+    ($(mod $module: ident = $name: literal {
+        $(fn $f: ident($($arg: ident: $ty: ty),*) $(-> $ret: ty)?;)*
+    })*) => {
+        $(
+            #[allow(clippy::missing_safety_doc)]
+            pub mod $module {
+                #[allow(unused_imports)]
+                use super::*;
+
+                #[cfg(target_arch = "wasm32")]
+                #[link(wasm_import_module = $name)]
+                extern "C" {
+                    $(pub fn $f($($arg: $ty),*) $(-> $ret)?;)*
+                }
+
+                $(
+                    #[cfg(not(target_arch = "wasm32"))]
+                    #[allow(unused_variables)]
+                    pub unsafe fn $f($($arg: $ty),*) $(-> $ret)? {
+                        panic!(concat!("`", $name, "::", stringify!($f), "` only exists on the console"))
+                    }
+                )*
+            }
+        )*
+    };
+}
+"#;
+
+/// Every generated file under `root`, the repository, and what it should contain.
+pub fn bindings(root: &Path, modules: &[AbiModule]) -> Vec<(PathBuf, String)> {
+    let mut files: Vec<(PathBuf, String)> = modules
+        .iter()
+        .map(|module| {
+            (
+                root.join("std")
+                    .join(format!("{}.h", header_name(module.name))),
+                c_header(module),
+            )
+        })
+        .collect();
+    files.push((root.join("sdk/src/sys.rs"), rust_sys(modules)));
+    files
+}
+
+/// `dbg`'s header was always called `debug.h`
+fn header_name(module: &str) -> &str {
+    match module {
+        "dbg" => "debug",
+        _ => module,
+    }
+}
+
+pub fn write_bindings(root: &Path, modules: &[AbiModule]) -> io::Result<()> {
+    for (path, contents) in bindings(root, modules) {
+        fs::write(path, contents)?;
+    }
+    Ok(())
+}
+
+/// The generated files under `root` that are missing, or differ from what they should be.
+pub fn stale_bindings(root: &Path, modules: &[AbiModule]) -> Vec<PathBuf> {
+    bindings(root, modules)
+        .into_iter()
+        .filter(|(path, contents)| fs::read_to_string(path).ok().as_ref() != Some(contents))
+        .map(|(path, _)| path)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasm::{self, HOST_ABI};
+
+    /// The lines of the item in `source` starting with `header`, without comments or attributes
+    fn sdk_item<'a>(source: &'a str, header: &str) -> Vec<&'a str> {
+        source
+            .lines()
+            .skip_while(|line| !line.trim_start().starts_with(header))
+            .skip(1)
+            .map(str::trim)
+            .take_while(|line| *line != "}")
+            .filter(|line| !line.starts_with("//") && !line.starts_with("#["))
+            .collect()
+    }
+
+    fn flags<T: bitflags::Flags>(flags: T) -> Vec<String>
+    where
+        T::Bits: std::fmt::Display,
+    {
+        flags
+            .iter_names()
+            .map(|(name, flag)| format!("const {name} = {};", flag.bits()))
+            .collect()
+    }
+
+    #[test]
+    fn bindings_are_up_to_date() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let stale = stale_bindings(root, HOST_ABI);
+        assert!(stale.is_empty(), "out of date, run `abi write`: {stale:?}");
+
+        // The SDK's own types for the host's aren't generated, so they have to be kept in step
+        let system = fs::read_to_string(root.join("sdk/src/system.rs")).unwrap();
+        let version = format!("pub const API_VERSION: u32 = {API_VERSION};");
+        assert!(
+            system.lines().any(|line| line == version),
+            "sdk API_VERSION"
+        );
+        assert_eq!(
+            sdk_item(&system, "pub struct Features"),
+            flags(Features::all())
+        );
+
+        let tile = fs::read_to_string(root.join("sdk/src/tile.rs")).unwrap();
+        let modes: Vec<String> = BlendMode::ALL
+            .iter()
+            .map(|mode| format!("{mode:?},"))
+            .collect();
+        assert_eq!(sdk_item(&tile, "pub enum BlendMode"), modes);
+        assert_eq!(
+            sdk_item(&tile, "pub struct BlendTargets"),
+            flags(BlendTargets::all())
+        );
+    }
+
+    #[test]
+    fn host_abi_matches_linker() {
+        assert_eq!(wasm::host_abi_mismatches().unwrap(), Vec::<String>::new());
+    }
+}
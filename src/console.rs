@@ -0,0 +1,121 @@
+use crate::{
+    frame::{FrameBuffer, HEIGHT},
+    gamepad::GamepadState,
+    ppu::Ppu,
+    savestate::SaveState,
+    wasm::{CartError, OverrunPolicy, WasmCart},
+};
+
+/// The console without any windowing, runs a cart and renders its frames.
+pub struct Console {
+    pub cart: WasmCart,
+    pub ppu: Ppu,
+}
+
+impl Console {
+    pub fn new(cart: WasmCart) -> Self {
+        Self {
+            cart,
+            ppu: Ppu::new(),
+        }
+    }
+
+    pub fn frame(&self) -> &FrameBuffer {
+        &self.ppu.frame
+    }
+
+    /// Sets the state the cart sees for a gamepad, until the next time it's set.
+    pub fn set_gamepad_state(&mut self, gamepad: usize, state: GamepadState) {
+        self.cart.state_mut().gamepads[gamepad] = state;
+    }
+
+    pub fn init(&mut self) -> Result<(), CartError> {
+        self.cart.init()
+    }
+
+    pub fn update(&mut self) -> Result<(), CartError> {
+        self.cart.update()
+    }
+
+    pub fn draw_frame(&mut self) -> Result<(), CartError> {
+        if self.cart.metrics().update_overrun
+            && let Some(budget) = self.cart.config().budget
+            && budget.overrun == OverrunPolicy::DropFrame
+        {
+            return Ok(());
+        }
+
+        self.cart.draw()?;
+        for y in 0..HEIGHT {
+            self.cart.v_blank(y as u32)?;
+            self.ppu.draw_scanline(&self.cart.state().tile_state, y);
+        }
+        Ok(())
+    }
+
+    /// Runs a single frame of the cart, then renders it.
+    pub fn run_frame(&mut self) -> Result<(), CartError> {
+        self.update()?;
+        self.draw_frame()
+    }
+
+    pub fn save_state(&mut self) -> wasmtime::Result<SaveState> {
+        self.cart.save_state()
+    }
+
+    pub fn load_state(&mut self, state: &SaveState) -> wasmtime::Result<()> {
+        self.cart.load_state(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasm::CartConfig;
+
+    /// Fills the top left cell with a solid tile of `color`, and saves `color`'s low byte
+    fn console(color: u32) -> Console {
+        let wat = format!(
+            r#"(module
+                (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+                (import "tile" "write_tile" (func $write_tile (param i32 i32)))
+                (import "tile" "set_background_tile" (func $set_background_tile (param i32 i32 i32 i32)))
+                (import "save" "write" (func $save_write (param i32 i32 i32)))
+                (memory (export "memory") 1)
+                (func (export "update")
+                    (memory.fill (i32.const 0) (i32.const 0x11) (i32.const 128))
+                    (i32.store8 (i32.const 128) (i32.const {color}))
+                    (call $write_palette (i32.const 0) (i32.const {color}))
+                    (call $write_tile (i32.const 1) (i32.const 0))
+                    (call $set_background_tile (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 1))
+                    (call $save_write (i32.const 0) (i32.const 128) (i32.const 1))))"#
+        );
+        let config = CartConfig {
+            cache_dir: None,
+            ..CartConfig::default()
+        };
+        Console::new(WasmCart::from_wat(&wat, config).unwrap())
+    }
+
+    #[test]
+    fn consoles_are_independent() {
+        let mut red = console(0xff0000);
+        let mut blue = console(0x0000ff);
+        red.run_frame().unwrap();
+        blue.run_frame().unwrap();
+
+        assert_eq!(red.frame().read_pixel(0, 0), 0xff0000.into());
+        assert_eq!(blue.frame().read_pixel(0, 0), 0x0000ff.into());
+        assert_eq!(red.cart.state().tile_state.palette[0], 0xff0000.into());
+        assert_eq!(blue.cart.state().tile_state.palette[0], 0x0000ff.into());
+        assert_eq!(red.cart.state().save_data.bytes[0], 0x00);
+        assert_eq!(blue.cart.state().save_data.bytes[0], 0xff);
+
+        // Saving and loading one doesn't touch the other
+        let state = red.save_state().unwrap();
+        red.load_state(&state).unwrap();
+        red.draw_frame().unwrap();
+        assert_eq!(blue.cart.state().tile_state.palette[0], 0x0000ff.into());
+        assert_eq!(blue.frame().read_pixel(0, 0), 0x0000ff.into());
+    }
+}
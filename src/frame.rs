@@ -34,6 +34,16 @@ impl FrameBuffer {
         self.data[start_pos + 1] = color.g;
         self.data[start_pos + 2] = color.b;
     }
+
+    pub fn read_pixel(&self, x: usize, y: usize) -> Color {
+        let start_pos = (x + y * WIDTH) * 4;
+        (
+            self.data[start_pos],
+            self.data[start_pos + 1],
+            self.data[start_pos + 2],
+        )
+            .into()
+    }
 }
 
 impl<'a> From<&'a FrameBuffer> for &'a [u8] {
@@ -1,13 +1,8 @@
-use std::{
-    collections::HashSet,
-    sync::{Mutex, MutexGuard, OnceLock},
-};
+use std::collections::HashSet;
 
 use bitflags::bitflags;
 use gilrs::{Button, EventType, GamepadId, Gilrs};
 
-static STATE: OnceLock<Mutex<GamepadStateManager>> = OnceLock::new();
-
 bitflags! {
     #[derive(Debug, Clone, Copy)]
     pub struct GamepadState: u16 {
@@ -46,19 +41,14 @@ pub struct GamepadStateManager {
     pub gamepads: [Gamepad; 4],
 }
 
-impl GamepadStateManager {
-    pub fn get() -> MutexGuard<'static, Self> {
-        STATE
-            .get_or_init(|| Mutex::new(Self::new()))
-            .lock()
-            .unwrap()
-    }
-
-    pub fn update() {
-        Self::get().pump_events();
+impl Default for GamepadStateManager {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    fn new() -> Self {
+impl GamepadStateManager {
+    pub fn new() -> Self {
         let gilrs = Gilrs::new().unwrap();
         let mut gil_gamepads = HashSet::new();
 
@@ -78,7 +68,7 @@ impl GamepadStateManager {
         }
     }
 
-    fn pump_events(&mut self) {
+    pub fn update(&mut self) {
         let mut inputted_gamepads = HashSet::new();
         while let Some(gilrs::Event { id, event, time: _ }) = self.gilrs.next_event() {
             match event {
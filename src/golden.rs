@@ -0,0 +1,300 @@
+use std::{
+    fmt::Display,
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    color::Color,
+    console::Console,
+    frame::{FrameBuffer, HEIGHT, WIDTH},
+    gamepad::GamepadState,
+};
+
+/// How many differing pixels are listed individually in a [`DiffReport`]
+const REPORTED_PIXELS: usize = 16;
+
+/// Sets a gamepad to the given state, starting at the given frame
+#[derive(Debug, Clone, Copy)]
+pub struct InputEvent {
+    pub frame: usize,
+    pub gamepad: usize,
+    pub state: GamepadState,
+}
+
+/// A list of input events, one per line in the form `<frame> <gamepad> <buttons>`,
+/// where buttons are `GamepadState` flag names separated by `|`.
+///
+/// ```text
+/// # Jump, then run right
+/// 0 0 South
+/// 4 0 Right
+/// 30 0
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct InputScript {
+    pub events: Vec<InputEvent>,
+}
+
+/// A cart run for a number of frames, compared against a stored image afterwards
+#[derive(Debug, Clone)]
+pub struct GoldenTest {
+    pub frames: usize,
+    pub inputs: InputScript,
+    pub golden: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum GoldenResult {
+    Matched,
+    Updated,
+    Missing,
+    Mismatch(DiffReport),
+}
+
+#[derive(Debug)]
+pub struct DiffReport {
+    /// The differing pixels, as (x, y, expected, actual)
+    pub pixels: Vec<(usize, usize, Color, Color)>,
+    pub actual_path: PathBuf,
+    pub diff_path: PathBuf,
+}
+
+impl InputScript {
+    pub fn parse(text: &str) -> wasmtime::Result<Self> {
+        let mut events = vec![];
+        for (line_idx, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(3, char::is_whitespace);
+            let frame = parts.next().unwrap_or_default().parse();
+            let gamepad = parts.next().unwrap_or_default().parse();
+            let state =
+                bitflags::parser::from_str::<GamepadState>(parts.next().unwrap_or_default());
+
+            let (Ok(frame), Ok(gamepad), Ok(state)) = (frame, gamepad, state) else {
+                return Err(wasmtime::Error::msg(format!(
+                    "invalid input event on line {}: `{line}`",
+                    line_idx + 1
+                )));
+            };
+
+            if gamepad >= 4 {
+                return Err(wasmtime::Error::msg(format!(
+                    "invalid gamepad {gamepad} on line {}",
+                    line_idx + 1
+                )));
+            }
+
+            events.push(InputEvent {
+                frame,
+                gamepad,
+                state,
+            });
+        }
+        events.sort_by_key(|event| event.frame);
+        Ok(Self { events })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> wasmtime::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+}
+
+impl GoldenTest {
+    /// Runs the loaded cart from `init`, then compares the last frame against the golden image.
+    ///
+    /// When `update` is set, the golden image is overwritten instead.
+    pub fn run(&self, console: &mut Console, update: bool) -> wasmtime::Result<GoldenResult> {
+        console.init()?;
+
+        let mut events = self.inputs.events.iter().peekable();
+        for frame in 0..self.frames {
+            while let Some(event) = events.next_if(|event| event.frame <= frame) {
+                console.set_gamepad_state(event.gamepad, event.state);
+            }
+            console.run_frame()?;
+        }
+
+        if update {
+            write_png(&self.golden, console.frame())?;
+            return Ok(GoldenResult::Updated);
+        }
+
+        if !self.golden.exists() {
+            return Ok(GoldenResult::Missing);
+        }
+
+        let expected = read_png(&self.golden)?;
+        let actual = console.frame();
+
+        let mut pixels = vec![];
+        let mut diff = Box::new(FrameBuffer::new());
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let expected = expected.read_pixel(x, y);
+                let actual = actual.read_pixel(x, y);
+                if expected == actual {
+                    // Dim the matching pixels so the differences stand out
+                    diff.write_pixel(
+                        x,
+                        y,
+                        (expected.r / 4, expected.g / 4, expected.b / 4).into(),
+                    );
+                } else {
+                    diff.write_pixel(x, y, (255, 0, 255).into());
+                    pixels.push((x, y, expected, actual));
+                }
+            }
+        }
+
+        if pixels.is_empty() {
+            return Ok(GoldenResult::Matched);
+        }
+
+        let actual_path = self.golden.with_extension("actual.png");
+        let diff_path = self.golden.with_extension("diff.png");
+        write_png(&actual_path, actual)?;
+        write_png(&diff_path, &diff)?;
+
+        Ok(GoldenResult::Mismatch(DiffReport {
+            pixels,
+            actual_path,
+            diff_path,
+        }))
+    }
+}
+
+impl Display for DiffReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (WIDTH, HEIGHT, 0, 0);
+        for (x, y, _, _) in &self.pixels {
+            min_x = min_x.min(*x);
+            min_y = min_y.min(*y);
+            max_x = max_x.max(*x);
+            max_y = max_y.max(*y);
+        }
+
+        writeln!(
+            f,
+            "{} of {} pixels differ, within ({min_x}, {min_y})..=({max_x}, {max_y})",
+            self.pixels.len(),
+            WIDTH * HEIGHT
+        )?;
+
+        for (x, y, expected, actual) in self.pixels.iter().take(REPORTED_PIXELS) {
+            writeln!(
+                f,
+                "  ({x}, {y}): expected #{:06x}, got #{:06x}",
+                u32::from(*expected),
+                u32::from(*actual)
+            )?;
+        }
+        if self.pixels.len() > REPORTED_PIXELS {
+            writeln!(f, "  ...and {} more", self.pixels.len() - REPORTED_PIXELS)?;
+        }
+
+        writeln!(f, "actual: {}", self.actual_path.display())?;
+        write!(f, "diff: {}", self.diff_path.display())
+    }
+}
+
+pub fn write_png(path: impl AsRef<Path>, frame: &FrameBuffer) -> wasmtime::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(writer, WIDTH as u32, HEIGHT as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(frame.data())?;
+    Ok(())
+}
+
+pub fn read_png(path: impl AsRef<Path>) -> wasmtime::Result<Box<FrameBuffer>> {
+    let path = path.as_ref();
+    let mut reader = png::Decoder::new(File::open(path)?).read_info()?;
+    let mut frame = Box::new(FrameBuffer::new());
+    let info = reader.next_frame(frame.data_mut())?;
+
+    if info.width as usize != WIDTH
+        || info.height as usize != HEIGHT
+        || info.color_type != png::ColorType::Rgba
+        || info.bit_depth != png::BitDepth::Eight
+    {
+        return Err(wasmtime::Error::msg(format!(
+            "{} is not a {WIDTH}x{HEIGHT} 8-bit RGBA image",
+            path.display()
+        )));
+    }
+
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::wasm::{CartConfig, WasmCart};
+
+    /// Runs every cart in `test_carts` like `make golden` does. C carts need clang, so they fail
+    /// until `make golden` has built them. Set `UPDATE_GOLDENS=1` to rewrite the PNGs.
+    #[test]
+    fn test_carts() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_carts");
+        let update = env::var("UPDATE_GOLDENS").is_ok_and(|value| value != "0");
+
+        let mut sources: Vec<PathBuf> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        sources.sort();
+
+        let mut failures = vec![];
+        for source in sources {
+            let cart = match source.extension().and_then(|ext| ext.to_str()) {
+                Some("wat") => source.clone(),
+                Some("c") if source.with_extension("wasm").exists() => {
+                    source.with_extension("wasm")
+                }
+                Some("c") => {
+                    failures.push(format!(
+                        "{}: not built, `make golden` builds it",
+                        source.display()
+                    ));
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let input = source.with_extension("input");
+            let test = GoldenTest {
+                frames: 60,
+                inputs: match input.exists() {
+                    true => InputScript::from_file(&input).unwrap(),
+                    false => InputScript::default(),
+                },
+                golden: source.with_extension("png"),
+            };
+            let config = CartConfig {
+                cache_dir: None,
+                ..CartConfig::default()
+            };
+            let mut console = Console::new(WasmCart::load(&cart, config).unwrap());
+
+            match test.run(&mut console, update).unwrap() {
+                GoldenResult::Matched | GoldenResult::Updated => {}
+                GoldenResult::Missing => failures.push(format!(
+                    "{}: no golden image, run with UPDATE_GOLDENS=1",
+                    cart.display()
+                )),
+                GoldenResult::Mismatch(report) => {
+                    failures.push(format!("{}: {report}", cart.display()))
+                }
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
@@ -1,29 +1,45 @@
 #![feature(stmt_expr_attributes, decl_macro, let_chains)]
 use std::{
+    path::PathBuf,
     thread::sleep,
     time::{Duration, Instant},
 };
 
+use console::Console;
 use egui_glium::egui_winit::egui;
 use egui_glium::{egui_winit::egui::ViewportId, EguiGlium};
-use frame::{FrameBuffer, HEIGHT, WIDTH};
+use frame::{HEIGHT, WIDTH};
 use gamepad::GamepadStateManager;
 use glium::{
     backend::glutin::SimpleWindowBuilder, glutin::surface::WindowSurface, implement_vertex,
     index::NoIndices, uniform, Display, Program, Surface, VertexBuffer,
 };
-use tile::{Sprite, TileState};
-use wasm::WasmCart;
+use reload::HotReload;
+use rewind::RewindBuffer;
+use savestate::SaveState;
+use wasm::{CartError, OverrunPolicy, WasmCart};
 use winit::{
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, WindowEvent},
     event_loop::{EventLoop, EventLoopWindowTarget},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
     window::Window,
 };
 
+pub mod abi;
 pub mod color;
+pub mod console;
 pub mod frame;
 pub mod gamepad;
+pub mod golden;
 pub mod math;
+pub mod metadata;
+pub mod package;
+pub mod ppu;
+pub mod reload;
+pub mod rewind;
+pub mod save_data;
+pub mod savestate;
+pub mod system;
 pub mod tile;
 pub mod wasm;
 
@@ -31,6 +47,12 @@ const VERTEX_SHADER: &str = include_str!("./shaders/vert.glsl");
 
 const FRAGMENT_SHADER: &str = include_str!("./shaders/frag.glsl");
 
+/// How many frames holding backspace can go back, about 20 seconds
+const REWIND_FRAMES: usize = 600;
+
+/// How often save data the cart has written is flushed to disk
+const SAVE_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Clone, Copy)]
 pub struct Vertex {
     position: (f32, f32),
@@ -41,7 +63,19 @@ implement_vertex!(Vertex, position location(0), uv location(1));
 pub struct WindowState {
     pub window: Window,
     pub display: Display<WindowSurface>,
-    pub frame: FrameBuffer,
+    pub console: Console,
+    pub gamepads: GamepadStateManager,
+    /// Set when the cart crashes, stops it from running until it's reset or reloaded
+    pub crash: Option<CartError>,
+    pub modifiers: ModifiersState,
+    pub rewind: RewindBuffer,
+    /// Whether the rewind key is held
+    pub rewinding: bool,
+    /// Set when the window loses focus or P is pressed, stops the cart from running
+    pub paused: bool,
+    pub hot_reload: HotReload,
+    /// When the save data was last flushed to disk
+    pub save_flushed: Instant,
     pub vertex_buffer: VertexBuffer<Vertex>,
     pub indices: NoIndices,
     pub shaders: Program,
@@ -49,14 +83,12 @@ pub struct WindowState {
 }
 
 impl WindowState {
-    pub fn new(event_loop: &EventLoop<()>) -> Self {
+    pub fn new(event_loop: &EventLoop<()>, console: Console) -> Self {
         let (window, display) = SimpleWindowBuilder::new()
-            .with_title("owo")
+            .with_title(&window_title(&console.cart))
             .with_inner_size(WIDTH as u32, HEIGHT as u32)
             .build(event_loop);
 
-        let frame = FrameBuffer::new();
-
         let shape = vec![
             Vertex {
                 position: (-1., 1.),
@@ -87,7 +119,15 @@ impl WindowState {
         Self {
             window,
             display,
-            frame,
+            console,
+            gamepads: GamepadStateManager::new(),
+            crash: None,
+            modifiers: ModifiersState::empty(),
+            rewind: RewindBuffer::new(REWIND_FRAMES),
+            rewinding: false,
+            paused: false,
+            hot_reload: HotReload::new(false),
+            save_flushed: Instant::now(),
             vertex_buffer,
             indices,
             shaders,
@@ -106,12 +146,32 @@ impl WindowState {
     fn event_handler(&mut self, event: Event<()>, window_target: &EventLoopWindowTarget<()>) {
         match event {
             Event::WindowEvent { event, .. } => {
-                match event {
+                match &event {
                     // This event is sent by the OS when you close the Window, or request the program to quit via the taskbar.
-                    WindowEvent::CloseRequested => window_target.exit(),
+                    WindowEvent::CloseRequested => {
+                        if self.crash.is_none()
+                            && let Err(err) = self.console.cart.on_exit()
+                        {
+                            eprintln!("{err}");
+                        }
+                        self.flush_save_data();
+                        window_target.exit();
+                    }
+                    WindowEvent::Focused(focused) => self.set_paused(!focused),
                     WindowEvent::RedrawRequested => self.render(),
                     winit::event::WindowEvent::Resized(window_size) => {
-                        self.display.resize(window_size.into());
+                        self.display.resize((*window_size).into());
+                    }
+                    WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+                    WindowEvent::KeyboardInput { event, .. }
+                        if event.physical_key == PhysicalKey::Code(KeyCode::Backspace) =>
+                    {
+                        self.rewinding = event.state == ElementState::Pressed;
+                    }
+                    WindowEvent::KeyboardInput { event, .. }
+                        if event.state == ElementState::Pressed && !event.repeat =>
+                    {
+                        self.key_pressed(event.physical_key);
                     }
                     _ => (),
                 }
@@ -124,67 +184,210 @@ impl WindowState {
         };
     }
 
+    /// P pauses and resumes, number keys load a save state from their slot, shift + number keys
+    /// save to it.
+    fn key_pressed(&mut self, key: PhysicalKey) {
+        let slot = match key {
+            PhysicalKey::Code(KeyCode::KeyP) => return self.set_paused(!self.paused),
+            PhysicalKey::Code(KeyCode::Digit1) => 1,
+            PhysicalKey::Code(KeyCode::Digit2) => 2,
+            PhysicalKey::Code(KeyCode::Digit3) => 3,
+            PhysicalKey::Code(KeyCode::Digit4) => 4,
+            PhysicalKey::Code(KeyCode::Digit5) => 5,
+            PhysicalKey::Code(KeyCode::Digit6) => 6,
+            PhysicalKey::Code(KeyCode::Digit7) => 7,
+            PhysicalKey::Code(KeyCode::Digit8) => 8,
+            PhysicalKey::Code(KeyCode::Digit9) => 9,
+            _ => return,
+        };
+
+        if self.modifiers.shift_key() {
+            self.save_state(slot);
+        } else {
+            self.load_state(slot);
+        }
+    }
+
+    /// Save states live next to the cart, as `<cart>.<slot>.state`
+    fn state_path(&self, slot: u32) -> Option<PathBuf> {
+        let path = self.console.cart.path();
+        if path.is_none() {
+            eprintln!("cart wasn't loaded from a file, so there's nowhere to keep save states");
+        }
+        path.map(|path| path.with_extension(format!("{slot}.state")))
+    }
+
+    fn save_state(&mut self, slot: u32) {
+        let Some(path) = self.state_path(slot) else {
+            return;
+        };
+        match self
+            .console
+            .save_state()
+            .and_then(|state| state.write(&path))
+        {
+            Ok(()) => println!("saved state to {}", path.display()),
+            Err(err) => eprintln!("couldn't save state to {}: {err}", path.display()),
+        }
+    }
+
+    fn load_state(&mut self, slot: u32) {
+        let Some(path) = self.state_path(slot) else {
+            return;
+        };
+        match SaveState::read(&path).and_then(|state| self.console.load_state(&state)) {
+            Ok(()) => {
+                println!("loaded state from {}", path.display());
+                self.crash = None;
+            }
+            Err(err) => eprintln!("couldn't load state from {}: {err}", path.display()),
+        }
+    }
+
     fn init(&mut self) {
-        WasmCart::init();
+        if let Err(err) = self.console.init() {
+            self.crashed(err);
+        }
     }
 
-    fn update(&mut self) {
-        GamepadStateManager::update();
+    /// Pauses or resumes the console, letting the cart know if it's changed.
+    fn set_paused(&mut self, paused: bool) {
+        if paused == self.paused {
+            return;
+        }
+        self.paused = paused;
 
-        WasmCart::update();
+        if self.crash.is_some() {
+            return;
+        }
+        let result = if paused {
+            self.console.cart.on_pause()
+        } else {
+            self.console.cart.on_resume()
+        };
+        if let Err(err) = result {
+            self.crashed(err);
+        }
     }
 
-    fn draw_frame(&mut self) {
-        for y in 0..HEIGHT {
-            WasmCart::v_blank(y as u32);
-            let tile_state = TileState::get();
-            let mut sprites: Vec<Sprite> = Vec::with_capacity(64);
-            for sprite in tile_state.sprites {
-                if (y as isize) >= sprite.position.1 as isize
-                    && (y as isize) < sprite.position.1 as isize + 16
-                    && sprite.visible
-                {
-                    sprites.push(sprite);
-                }
+    fn update(&mut self) -> Result<(), CartError> {
+        self.gamepads.update();
+        for (i, gamepad) in self.gamepads.gamepads.iter().enumerate() {
+            self.console.set_gamepad_state(i, gamepad.state);
+        }
+
+        self.console.update()
+    }
+
+    fn crashed(&mut self, err: CartError) {
+        eprintln!("{err}");
+        if let Some(backtrace) = err.backtrace() {
+            eprintln!("{backtrace}");
+        }
+        self.crash = Some(err);
+    }
+
+    fn flush_save_data(&mut self) {
+        self.save_flushed = Instant::now();
+        if let Err(err) = self.console.cart.flush_save_data() {
+            eprintln!("couldn't write save data: {err}");
+        }
+    }
+
+    /// Restarts the cart from `init`, with a fresh instance.
+    fn reset(&mut self) {
+        if self.crash.is_none()
+            && let Err(err) = self.console.cart.on_reset()
+        {
+            eprintln!("{err}");
+        }
+        self.crash = None;
+        self.rewind.clear();
+        match self.console.cart.reset() {
+            Ok(()) => self.init(),
+            Err(err) => self.crashed(err),
+        }
+    }
+
+    /// Loads the cart from disk again, then restarts it.
+    fn reload(&mut self) {
+        let Some(path) = self.console.cart.path() else {
+            return self.reset();
+        };
+        let path = path.to_owned();
+        self.flush_save_data();
+        self.crash = None;
+        self.rewind.clear();
+        match WasmCart::load(path, self.console.cart.config().clone()) {
+            Ok(cart) => {
+                self.console.cart = cart;
+                self.window.set_title(&window_title(&self.console.cart));
+                self.init();
             }
-            sprites.sort_by(|a, b| a.position.cmp(&b.position));
-            for x in 0..WIDTH {
-                let mut color = tile_state.palette[tile_state.background_color as usize];
-                for i in 0..8 {
-                    if !tile_state.backgrounds[i].visible {
-                        continue;
-                    }
-                    let (palette_offset, palette) =
-                        tile_state.backgrounds[i].get_color_offset(&tile_state, x, y);
-                    if palette_offset != 0 {
-                        color = tile_state.palette[palette as usize + palette_offset as usize - 1];
-                        break;
-                    }
-                }
-                for sprite in sprites.iter() {
-                    if (x as isize) >= sprite.position.0 as isize
-                        && (x as isize) < sprite.position.0 as isize + 16
-                    {
-                        let (palette_offset, palette) = sprite.get_color_offset(&tile_state, x, y);
-                        if palette_offset != 0 {
-                            color =
-                                tile_state.palette[palette as usize + palette_offset as usize - 1];
-                            break;
-                        }
-                    }
+            Err(err) => self.crashed(CartError::load(err)),
+        }
+    }
+
+    /// Reloads the cart when its file changes, keeping the tile state.
+    fn check_for_changes(&mut self) {
+        if !self.hot_reload.changed(&self.console.cart) {
+            return;
+        }
+
+        match self
+            .hot_reload
+            .reload(&mut self.console, self.crash.is_some())
+        {
+            // Most likely the file is still being written, it'll be tried again when it changes
+            Err(CartError::Load { message }) => eprintln!("couldn't reload cart: {message}"),
+            result => {
+                println!("cart changed, reloaded it");
+                self.window.set_title(&window_title(&self.console.cart));
+                self.crash = None;
+                self.rewind.clear();
+                if let Err(err) = result {
+                    self.crashed(err);
                 }
-                self.frame.write_pixel(x, y, color);
             }
         }
     }
 
+    /// Runs the next frame, or steps back one while rewinding.
+    fn run_frame(&mut self) -> Result<(), CartError> {
+        if self.rewinding {
+            let Some(state) = self.rewind.pop() else {
+                return Ok(());
+            };
+            self.crash = None;
+            self.console.load_state(&state).map_err(CartError::load)?;
+            return self.console.draw_frame();
+        }
+
+        if self.crash.is_some() || self.paused {
+            return Ok(());
+        }
+
+        self.update()?;
+        self.console.draw_frame()?;
+        match self.console.save_state() {
+            Ok(state) => self.rewind.push(&state),
+            Err(err) => eprintln!("couldn't record frame for rewinding: {err}"),
+        }
+        Ok(())
+    }
+
     fn render(&mut self) {
         let start = Instant::now();
-        self.update();
-        self.draw_frame();
+        self.check_for_changes();
+        if let Err(err) = self.run_frame() {
+            self.crashed(err);
+        }
+        if self.save_flushed.elapsed() >= SAVE_FLUSH_INTERVAL {
+            self.flush_save_data();
+        }
 
         let image = glium::texture::RawImage2d::from_raw_rgba_reversed(
-            self.frame.data(),
+            self.console.frame().data(),
             (WIDTH as u32, HEIGHT as u32),
         );
         let texture = glium::texture::Texture2d::new(&self.display, image).unwrap();
@@ -209,12 +412,57 @@ impl WindowState {
             )
             .unwrap();
 
+        let mut reset = false;
+        let mut reload = false;
+        let memory_size = self.console.cart.memory_size();
         self.egui.run(&self.window, |egui_ctx| {
+            egui::Window::new("Cart")
+                .resizable(true)
+                .max_width(1024.)
+                .show(egui_ctx, |ui| {
+                    let cart = &self.console.cart;
+                    let metadata = cart.metadata();
+                    let unknown = || "unknown".to_string();
+                    ui.label(format!(
+                        "Title: {}",
+                        metadata.title.clone().unwrap_or_else(unknown)
+                    ));
+                    ui.label(format!(
+                        "Author: {}",
+                        metadata.author.clone().unwrap_or_else(unknown)
+                    ));
+                    if let Some(version) = &metadata.version {
+                        ui.label(format!("Version: {version}"));
+                    }
+                    ui.label(format!(
+                        "API version: {}",
+                        metadata
+                            .api_version
+                            .map_or_else(unknown, |version| version.to_string())
+                    ));
+                    ui.label(format!(
+                        "Gamepads: {}",
+                        metadata
+                            .gamepads
+                            .map_or_else(unknown, |gamepads| gamepads.to_string())
+                    ));
+                    let id: String = cart.id()[..8]
+                        .iter()
+                        .map(|byte| format!("{byte:02x}"))
+                        .collect();
+                    ui.label(format!("ID: {id}"));
+                    ui.label(format!(
+                        "Memory: {} / {} KiB",
+                        memory_size / 1024,
+                        cart.config().memory_limit / 1024
+                    ));
+                });
+
             egui::Window::new("Background State")
                 .resizable(true)
                 .max_width(1024.)
                 .show(egui_ctx, |ui| {
-                    let backgrounds = &TileState::get().backgrounds;
+                    let backgrounds = &self.console.cart.state().tile_state.backgrounds;
                     for i in 0..8 {
                         let bg = backgrounds[i];
                         ui.heading(format!("BG{}", i));
@@ -226,10 +474,82 @@ impl WindowState {
                         ));
                     }
                 });
+
+            let metrics = self.console.cart.metrics();
+            if let Some(budget) = self.console.cart.config().budget {
+                egui::Window::new("CPU")
+                    .resizable(true)
+                    .max_width(1024.)
+                    .show(egui_ctx, |ui| {
+                        let percent = |used: u64, budget: u64| used as f64 / budget as f64 * 100.;
+                        ui.label(format!(
+                            "Update: {} ({:.1}%)",
+                            metrics.update,
+                            percent(metrics.update, budget.frame)
+                        ));
+                        ui.label(format!(
+                            "Draw: {} ({:.1}%)",
+                            metrics.draw,
+                            percent(metrics.draw, budget.frame)
+                        ));
+                        ui.label(format!(
+                            "V-blank: {} total, {} max ({:.1}%)",
+                            metrics.v_blank_total,
+                            metrics.v_blank_max,
+                            percent(metrics.v_blank_max, budget.scanline)
+                        ));
+                        ui.label(format!(
+                            "Overruns: {} update, {} draw, {} v-blank",
+                            metrics.update_overrun as u32,
+                            metrics.draw_overrun as u32,
+                            metrics.v_blank_overruns
+                        ));
+                    });
+
+                if metrics.overrun() && budget.overrun == OverrunPolicy::Slowdown {
+                    egui::Area::new(egui::Id::new("slowdown"))
+                        .anchor(egui::Align2::RIGHT_TOP, [-8., 8.])
+                        .show(egui_ctx, |ui| {
+                            ui.colored_label(egui::Color32::YELLOW, "SLOWDOWN");
+                        });
+                }
+            }
+
+            if self.paused {
+                egui::Area::new(egui::Id::new("paused"))
+                    .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+                    .show(egui_ctx, |ui| {
+                        ui.colored_label(egui::Color32::WHITE, "PAUSED");
+                    });
+            }
+
+            if let Some(crash) = &self.crash {
+                egui::Window::new("Cart Crashed")
+                    .resizable(true)
+                    .max_width(1024.)
+                    .show(egui_ctx, |ui| {
+                        ui.colored_label(egui::Color32::RED, crash.to_string());
+                        if let Some(backtrace) = crash.backtrace() {
+                            egui::ScrollArea::vertical().show(ui, |ui| {
+                                ui.monospace(backtrace);
+                            });
+                        }
+                        ui.horizontal(|ui| {
+                            reset = ui.button("Reset").clicked();
+                            reload = ui.button("Reload").clicked();
+                        });
+                    });
+            }
         });
         self.egui.paint(&self.display, &mut target);
         target.finish().unwrap();
 
+        if reset {
+            self.reset();
+        } else if reload {
+            self.reload();
+        }
+
         let elapsed = start.elapsed();
 
         if elapsed.as_millis() >= 32 {
@@ -239,3 +559,16 @@ impl WindowState {
         sleep(Duration::from_millis(32) - elapsed)
     }
 }
+
+/// The cart's title, or its file name if it doesn't have one.
+fn window_title(cart: &WasmCart) -> String {
+    cart.metadata()
+        .title
+        .clone()
+        .or_else(|| {
+            cart.path()
+                .and_then(|path| path.file_stem())
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "console".to_string())
+}
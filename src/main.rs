@@ -1,12 +1,266 @@
 #![feature(stmt_expr_attributes, decl_macro)]
-use std::env;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
-use console::{wasm::WasmCart, WindowState};
+use console::{
+    abi,
+    console::Console,
+    golden::{GoldenResult, GoldenTest, InputScript},
+    package::{Assets, CartPackage, PackageMetadata},
+    tile::Tile,
+    wasm::{self, CartConfig, WasmCart},
+    WindowState,
+};
 use winit::event_loop::EventLoop;
 
-fn main() {
+fn main() -> ExitCode {
     let args: Vec<String> = env::args().collect();
-    WasmCart::load(&args[1]).unwrap();
+
+    if args.get(1).map(String::as_str) == Some("golden") {
+        return golden(&args[2..]);
+    }
+    if args.get(1).map(String::as_str) == Some("pack") {
+        return pack(&args[2..]);
+    }
+    if args.get(1).map(String::as_str) == Some("abi") {
+        return abi(&args[2..]);
+    }
+
+    let mut config = CartConfig::default();
+    if args[2..].iter().any(|arg| arg == "--no-cache") {
+        config.cache_dir = None;
+    }
+    let cart = WasmCart::load(&args[1], config).unwrap();
     let event_loop = EventLoop::new().unwrap();
-    WindowState::new(&event_loop).run_event_loop(event_loop);
+    let mut window = WindowState::new(&event_loop, Console::new(cart));
+    window.hot_reload.keep_memory = args[2..].iter().any(|arg| arg == "--keep-memory");
+    window.run_event_loop(event_loop);
+    ExitCode::SUCCESS
+}
+
+/// `golden <cart> <golden.png> [--frames N] [--input <script>] [--update]`
+///
+/// Setting `UPDATE_GOLDENS=1` is the same as passing `--update`.
+fn golden(args: &[String]) -> ExitCode {
+    let [cart, golden, flags @ ..] = args else {
+        eprintln!("usage: golden <cart> <golden.png> [--frames N] [--input <script>] [--update]");
+        return ExitCode::FAILURE;
+    };
+
+    let mut test = GoldenTest {
+        frames: 1,
+        inputs: InputScript::default(),
+        golden: PathBuf::from(golden),
+    };
+    let mut update = env::var("UPDATE_GOLDENS").is_ok_and(|value| value != "0");
+
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        match (flag.as_str(), flags.clone().next()) {
+            ("--frames", Some(frames)) => {
+                let Ok(frames) = frames.parse() else {
+                    eprintln!("invalid frame count `{frames}`");
+                    return ExitCode::FAILURE;
+                };
+                test.frames = frames;
+                flags.next();
+            }
+            ("--input", Some(path)) => {
+                test.inputs = match InputScript::from_file(path) {
+                    Ok(inputs) => inputs,
+                    Err(err) => {
+                        eprintln!("{path}: {err}");
+                        return ExitCode::FAILURE;
+                    }
+                };
+                flags.next();
+            }
+            ("--update", _) => update = true,
+            _ => {
+                eprintln!("unknown argument `{flag}`");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let mut console = match WasmCart::load(cart, CartConfig::default()) {
+        Ok(cart) => Console::new(cart),
+        Err(err) => {
+            eprintln!("{cart}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match test.run(&mut console, update) {
+        Ok(GoldenResult::Matched) => {
+            println!("{cart}: ok");
+            ExitCode::SUCCESS
+        }
+        Ok(GoldenResult::Updated) => {
+            println!("{cart}: updated {}", test.golden.display());
+            ExitCode::SUCCESS
+        }
+        Ok(GoldenResult::Missing) => {
+            eprintln!(
+                "{cart}: {} doesn't exist, run with --update to create it",
+                test.golden.display()
+            );
+            ExitCode::FAILURE
+        }
+        Ok(GoldenResult::Mismatch(report)) => {
+            eprintln!("{cart}: frame {} doesn't match\n{report}", test.frames);
+            ExitCode::FAILURE
+        }
+        Err(err) => {
+            eprintln!("{cart}: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// `pack <cart.wasm> <out.cart> --title <title> [--author <author>] [--version <version>]
+/// [--icon <tile>] [--asset <name>=<path>]...`
+///
+/// The icon is a file with the tile's 128 bytes, in the same layout `tile_WriteTile` takes.
+fn pack(args: &[String]) -> ExitCode {
+    const USAGE: &str = "usage: pack <cart.wasm> <out.cart> --title <title> [--author <author>] \
+        [--version <version>] [--icon <tile>] [--asset <name>=<path>]...";
+
+    let [wasm, out, flags @ ..] = args else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    let mut metadata = PackageMetadata::default();
+    let mut assets = Assets::new();
+
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        let Some(value) = flags.next() else {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        };
+        match flag.as_str() {
+            "--title" => metadata.title = value.clone(),
+            "--author" => metadata.author = value.clone(),
+            "--version" => metadata.version = value.clone(),
+            "--icon" => {
+                let icon = fs::read(value)
+                    .map_err(|err| err.to_string())
+                    .and_then(|bytes| {
+                        bytes.try_into().map_err(|bytes: Vec<u8>| {
+                            format!("is {} bytes, expected 128", bytes.len())
+                        })
+                    });
+                match icon {
+                    Ok(icon) => metadata.icon = Some(Tile(icon)),
+                    Err(err) => {
+                        eprintln!("{value}: {err}");
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+            "--asset" => {
+                let Some((name, path)) = value.split_once('=') else {
+                    eprintln!("assets should be given as <name>=<path>, not `{value}`");
+                    return ExitCode::FAILURE;
+                };
+                let data = match fs::read(path) {
+                    Ok(data) => data,
+                    Err(err) => {
+                        eprintln!("{path}: {err}");
+                        return ExitCode::FAILURE;
+                    }
+                };
+                if assets.insert(name.to_string(), data).is_some() {
+                    eprintln!("asset `{name}` was given twice");
+                    return ExitCode::FAILURE;
+                }
+            }
+            _ => {
+                eprintln!("unknown argument `{flag}`");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let wasm = match fs::read(wasm) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("{wasm}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let package = CartPackage {
+        metadata,
+        wasm,
+        assets,
+    };
+    if let Err(err) = package.validate() {
+        eprintln!("{out}: {err:#}");
+        return ExitCode::FAILURE;
+    }
+    if let Err(err) = package.write(out) {
+        eprintln!("{out}: {err}");
+        return ExitCode::FAILURE;
+    }
+
+    println!("packed {out}");
+    ExitCode::SUCCESS
+}
+
+/// `abi write|check [<repo>]`
+///
+/// `write` generates the C headers in `std/` and the Rust SDK's raw imports from the host API,
+/// `check` fails if they're out of date, or the host's functions don't match their descriptions.
+fn abi(args: &[String]) -> ExitCode {
+    let (command, root) = match args {
+        [command] => (command, Path::new(".")),
+        [command, root] => (command, Path::new(root)),
+        _ => {
+            eprintln!("usage: abi write|check [<repo>]");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match command.as_str() {
+        "write" => match abi::write_bindings(root, wasm::HOST_ABI) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("{err}");
+                ExitCode::FAILURE
+            }
+        },
+        "check" => {
+            let mismatches = match wasm::host_abi_mismatches() {
+                Ok(mismatches) => mismatches,
+                Err(err) => {
+                    eprintln!("{err}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            for mismatch in &mismatches {
+                eprintln!("{mismatch}");
+            }
+            let stale = abi::stale_bindings(root, wasm::HOST_ABI);
+            for path in &stale {
+                eprintln!("{} is out of date, run `abi write`", path.display());
+            }
+
+            if mismatches.is_empty() && stale.is_empty() {
+                println!("bindings are up to date");
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        _ => {
+            eprintln!("unknown command `{command}`, expected write or check");
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -15,6 +15,11 @@ impl Fixed {
     pub fn from_float(val: f32) -> Self {
         Self((val * 256.) as i16)
     }
+
+    /// The underlying 8.8 value, the inverse of [`Fixed::from`]
+    pub fn raw(self) -> i16 {
+        self.0
+    }
 }
 
 impl Default for Fixed {
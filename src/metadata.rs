@@ -0,0 +1,206 @@
+use crate::package::PackageMetadata;
+
+/// The custom section carts describe themselves in
+pub const SECTION_NAME: &str = "cart_metadata";
+
+/// What a cart says about itself, from its `cart_metadata` custom section or its package.
+///
+/// The section is UTF-8 text, with a `key=value` pair on each line. Each key can only be given
+/// once. Unknown keys are ignored, so newer carts still load.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CartMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// The version of the cart itself, only packages have one
+    pub version: Option<String>,
+    /// The version of the host API the cart was built against
+    pub api_version: Option<u32>,
+    /// How many gamepads the cart would like, from 1 to 4
+    pub gamepads: Option<u8>,
+}
+
+impl CartMetadata {
+    /// Reads the metadata from a wasm module's custom section. Modules without one, or that aren't
+    /// in the binary format, have no metadata.
+    pub fn from_wasm(wasm: &[u8]) -> wasmtime::Result<Self> {
+        match find_custom_section(wasm, SECTION_NAME) {
+            Some(section) => Self::parse(section),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn parse(section: &[u8]) -> wasmtime::Result<Self> {
+        // C strings put in a section keep their nul terminator
+        let text = std::str::from_utf8(section)?.trim_end_matches('\0');
+        let mut metadata = Self::default();
+        let mut keys = vec![];
+
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(wasmtime::Error::msg(format!(
+                    "metadata line `{line}` isn't a key=value pair"
+                )));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            if keys.contains(&key) {
+                return Err(wasmtime::Error::msg(format!(
+                    "metadata key `{key}` is given more than once"
+                )));
+            }
+            keys.push(key);
+            match key {
+                "title" => metadata.title = Some(value.to_string()),
+                "author" => metadata.author = Some(value.to_string()),
+                "api_version" => {
+                    metadata.api_version = Some(value.parse().map_err(|_| {
+                        wasmtime::Error::msg(format!("invalid api_version `{value}`"))
+                    })?)
+                }
+                "gamepads" => match value.parse() {
+                    Ok(gamepads @ 1..=4) => metadata.gamepads = Some(gamepads),
+                    _ => {
+                        return Err(wasmtime::Error::msg(format!(
+                            "gamepads should be from 1 to 4, not `{value}`"
+                        )));
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    /// Fills in the metadata from a package, which wins over the custom section.
+    pub fn merge_package(&mut self, package: &PackageMetadata) {
+        self.title = Some(package.title.clone());
+        for (field, value) in [
+            (&mut self.author, &package.author),
+            (&mut self.version, &package.version),
+        ] {
+            if !value.is_empty() {
+                *field = Some(value.clone());
+            }
+        }
+    }
+}
+
+/// Finds the first custom section called `name` in a wasm binary.
+fn find_custom_section<'a>(wasm: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    let mut bytes = wasm.strip_prefix(b"\0asm")?.get(4..)?;
+
+    while let Some((&id, rest)) = bytes.split_first() {
+        let (size, rest) = read_leb128(rest)?;
+        let section = rest.get(..size as usize)?;
+        bytes = &rest[size as usize..];
+
+        if id != 0 {
+            continue;
+        }
+        let (name_len, section) = read_leb128(section)?;
+        let section_name = section.get(..name_len as usize)?;
+        if section_name == name.as_bytes() {
+            return Some(&section[name_len as usize..]);
+        }
+    }
+
+    None
+}
+
+/// Reads an unsigned LEB128 u32, returning it and the bytes after it.
+fn read_leb128(bytes: &[u8]) -> Option<(u32, &[u8])> {
+    let mut value = 0u32;
+    for (i, &byte) in bytes.iter().enumerate().take(5) {
+        value |= ((byte & 0x7f) as u32) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A module with just a custom section
+    fn module(name: &str, contents: &[u8]) -> Vec<u8> {
+        let mut section = vec![name.len() as u8];
+        section.extend_from_slice(name.as_bytes());
+        section.extend_from_slice(contents);
+
+        let mut wasm = b"\0asm\x01\0\0\0\0".to_vec();
+        wasm.push(section.len() as u8);
+        wasm.extend(section);
+        wasm
+    }
+
+    #[test]
+    fn reads_the_section() {
+        let wasm = module(
+            SECTION_NAME,
+            b"title = Game\nauthor=Someone\n\napi_version=1\ngamepads=2\nunknown=x\0",
+        );
+        let metadata = CartMetadata::from_wasm(&wasm).unwrap();
+        assert_eq!(
+            metadata,
+            CartMetadata {
+                title: Some("Game".to_string()),
+                author: Some("Someone".to_string()),
+                version: None,
+                api_version: Some(1),
+                gamepads: Some(2),
+            }
+        );
+    }
+
+    #[test]
+    fn other_sections_are_skipped() {
+        let mut wasm = module("name", b"title=Other");
+        wasm.extend_from_slice(&module(SECTION_NAME, b"title=Game")[8..]);
+        let metadata = CartMetadata::from_wasm(&wasm).unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Game"));
+    }
+
+    #[test]
+    fn truncated_sections_are_missing() {
+        let wasm = module(SECTION_NAME, b"title=Game");
+        let truncated = &wasm[..wasm.len() - 1];
+        assert_eq!(
+            CartMetadata::from_wasm(truncated).unwrap(),
+            CartMetadata::default()
+        );
+        // Cut off inside the section's name, or its size
+        assert_eq!(
+            CartMetadata::from_wasm(&wasm[..12]).unwrap(),
+            CartMetadata::default()
+        );
+        assert_eq!(
+            CartMetadata::from_wasm(&wasm[..9]).unwrap(),
+            CartMetadata::default()
+        );
+    }
+
+    #[test]
+    fn duplicate_keys_are_an_error() {
+        let err = CartMetadata::parse(b"title=Game\ntitle = Other").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "metadata key `title` is given more than once"
+        );
+    }
+
+    #[test]
+    fn non_utf8_is_an_error() {
+        assert!(CartMetadata::parse(b"title=\xff\xfe").is_err());
+        let wasm = module(SECTION_NAME, b"author=\xc3");
+        assert!(CartMetadata::from_wasm(&wasm).is_err());
+    }
+
+    #[test]
+    fn invalid_values_are_an_error() {
+        assert!(CartMetadata::parse(b"title").is_err());
+        assert!(CartMetadata::parse(b"api_version=one").is_err());
+        assert!(CartMetadata::parse(b"gamepads=5").is_err());
+    }
+}
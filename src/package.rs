@@ -0,0 +1,287 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use wasmtime::{Engine, ExternType, Module};
+
+use crate::{savestate::Reader, tile::Tile};
+
+const MAGIC: &[u8; 4] = b"FCRT";
+/// Bumped whenever the layout changes, older packages can't be loaded
+pub const VERSION: u32 = 1;
+
+/// How long the title, author and version can be, in bytes
+pub const MAX_STRING_LEN: usize = 256;
+
+/// Named blobs of data packaged with a cart, which it can read through the `asset` module
+pub type Assets = BTreeMap<String, Vec<u8>>;
+
+/// A cart packaged into a single file, with its metadata and assets.
+///
+/// The layout is the magic and version, the metadata, the wasm module, then the assets. Numbers
+/// are little endian, strings and blobs are prefixed with their length as a u32.
+#[derive(Debug, Clone)]
+pub struct CartPackage {
+    pub metadata: PackageMetadata,
+    pub wasm: Vec<u8>,
+    pub assets: Assets,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PackageMetadata {
+    pub title: String,
+    pub author: String,
+    pub version: String,
+    /// A tile to show the cart with, drawn with the first 16 palette entries
+    pub icon: Option<Tile>,
+}
+
+impl CartPackage {
+    /// Whether the bytes look like a package, rather than a bare wasm module
+    pub fn is_package(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> wasmtime::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> wasmtime::Result<()> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.wasm.len() + 1024);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+
+        for string in [
+            &self.metadata.title,
+            &self.metadata.author,
+            &self.metadata.version,
+        ] {
+            write_bytes(&mut out, string.as_bytes());
+        }
+        match &self.metadata.icon {
+            Some(icon) => {
+                out.push(1);
+                out.extend_from_slice(&icon.0);
+            }
+            None => out.push(0),
+        }
+
+        write_bytes(&mut out, &self.wasm);
+
+        out.extend_from_slice(&(self.assets.len() as u32).to_le_bytes());
+        for (name, data) in &self.assets {
+            write_bytes(&mut out, name.as_bytes());
+            write_bytes(&mut out, data);
+        }
+
+        out
+    }
+
+    /// Parses a package, checking its layout but not the wasm module, see
+    /// [`CartPackage::validate`] for that.
+    pub fn from_bytes(bytes: &[u8]) -> wasmtime::Result<Self> {
+        let mut reader = Reader::new(bytes, "cart package");
+
+        if reader.take(4)? != MAGIC {
+            return Err(wasmtime::Error::msg("not a cart package"));
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(wasmtime::Error::msg(format!(
+                "cart package is version {version}, expected {VERSION}"
+            )));
+        }
+
+        let metadata = PackageMetadata {
+            title: reader.string()?,
+            author: reader.string()?,
+            version: reader.string()?,
+            icon: match reader.u8()? {
+                0 => None,
+                1 => Some(Tile(reader.take(128)?.try_into()?)),
+                flag => return Err(wasmtime::Error::msg(format!("invalid icon flag {flag}"))),
+            },
+        };
+
+        let len = reader.u32()? as usize;
+        let wasm = reader.take(len)?.to_vec();
+
+        let mut assets = Assets::new();
+        for _ in 0..reader.u32()? {
+            let name = reader.string()?;
+            let len = reader.u32()? as usize;
+            let data = reader.take(len)?.to_vec();
+            if assets.insert(name.clone(), data).is_some() {
+                return Err(wasmtime::Error::msg(format!(
+                    "asset `{name}` is in the package twice"
+                )));
+            }
+        }
+
+        if !reader.is_empty() {
+            return Err(wasmtime::Error::msg("cart package has trailing bytes"));
+        }
+
+        let package = Self {
+            metadata,
+            wasm,
+            assets,
+        };
+        package.check_metadata()?;
+        Ok(package)
+    }
+
+    fn check_metadata(&self) -> wasmtime::Result<()> {
+        if self.metadata.title.is_empty() {
+            return Err(wasmtime::Error::msg("cart package has no title"));
+        }
+        for (field, value) in [
+            ("title", &self.metadata.title),
+            ("author", &self.metadata.author),
+            ("version", &self.metadata.version),
+        ] {
+            if value.len() > MAX_STRING_LEN {
+                return Err(wasmtime::Error::msg(format!(
+                    "{field} is {} bytes, it can be at most {MAX_STRING_LEN}",
+                    value.len()
+                )));
+            }
+        }
+        if self.assets.contains_key("") {
+            return Err(wasmtime::Error::msg("assets need a name"));
+        }
+        Ok(())
+    }
+
+    /// Checks everything that would stop the package from loading: the metadata, that the wasm
+    /// module is valid, and that the functions it exports for the console have the right types.
+    pub fn validate(&self) -> wasmtime::Result<()> {
+        self.check_metadata()?;
+
+        let engine = Engine::default();
+        let module = Module::from_binary(&engine, &self.wasm)?;
+
+        // The parameters each export takes, none of them return anything
+        let exports: [(&str, &[&str]); 8] = [
+            ("init", &[]),
+            ("update", &[]),
+            ("draw", &[]),
+            ("v_blank", &["i32"]),
+            ("on_pause", &[]),
+            ("on_resume", &[]),
+            ("on_reset", &[]),
+            ("on_exit", &[]),
+        ];
+        for (name, params) in exports {
+            match module.get_export(name) {
+                Some(ExternType::Func(ty))
+                    if ty.results().len() == 0
+                        && ty
+                            .params()
+                            .map(|ty| ty.to_string())
+                            .eq(params.iter().copied()) => {}
+                Some(ExternType::Func(_)) => {
+                    return Err(wasmtime::Error::msg(format!(
+                        "`{name}` should take ({}) and return nothing",
+                        params.join(", ")
+                    )));
+                }
+                Some(_) => {
+                    return Err(wasmtime::Error::msg(format!("`{name}` isn't a function")));
+                }
+                None => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasm::{CartConfig, WasmCart};
+
+    fn package(wat: &str) -> CartPackage {
+        CartPackage {
+            metadata: PackageMetadata {
+                title: "Test".to_string(),
+                author: "Someone".to_string(),
+                version: "1.0".to_string(),
+                icon: Some(Tile([7; 128])),
+            },
+            wasm: wat::parse_str(wat).unwrap(),
+            assets: Assets::from([
+                ("level".to_string(), vec![1, 2, 3]),
+                ("empty".to_string(), vec![]),
+            ]),
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let package = package("(module (func (export \"update\")))");
+        let bytes = package.to_bytes();
+        assert!(CartPackage::is_package(&bytes));
+
+        let read = CartPackage::from_bytes(&bytes).unwrap();
+        assert_eq!(read.metadata.title, "Test");
+        assert_eq!(read.metadata.author, "Someone");
+        assert_eq!(read.metadata.version, "1.0");
+        assert_eq!(read.metadata.icon.map(|icon| icon.0), Some([7; 128]));
+        assert_eq!(read.wasm, package.wasm);
+        assert_eq!(read.assets, package.assets);
+        read.validate().unwrap();
+
+        let config = CartConfig {
+            cache_dir: None,
+            ..CartConfig::default()
+        };
+        let cart = WasmCart::from_bytes(&bytes, config).unwrap();
+        assert_eq!(cart.package().unwrap().title, "Test");
+        assert_eq!(cart.state().assets["level"], [1, 2, 3]);
+    }
+
+    #[test]
+    fn rejects_bad_packages() {
+        let bytes = package("(module)").to_bytes();
+        for len in [0, 4, 8, bytes.len() - 1] {
+            assert!(CartPackage::from_bytes(&bytes[..len]).is_err());
+        }
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(CartPackage::from_bytes(&trailing).is_err());
+
+        let mut version = bytes;
+        version[4] = 0xff;
+        assert!(CartPackage::from_bytes(&version).is_err());
+
+        let mut untitled = package("(module)");
+        untitled.metadata.title.clear();
+        assert!(CartPackage::from_bytes(&untitled.to_bytes()).is_err());
+    }
+
+    #[test]
+    fn validate_checks_export_types() {
+        package("(module (func (export \"v_blank\") (param i32)))")
+            .validate()
+            .unwrap();
+        assert!(package("(module (func (export \"update\") (param i32)))")
+            .validate()
+            .is_err());
+        assert!(
+            package("(module (global (export \"draw\") i32 (i32.const 0)))")
+                .validate()
+                .is_err()
+        );
+    }
+}
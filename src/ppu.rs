@@ -0,0 +1,218 @@
+use crate::{
+    color::Color,
+    frame::{FrameBuffer, WIDTH},
+    tile::{BlendMode, BlendTargets, Sprite, TileState, BACKGROUND_COUNT, SPRITE_COUNT},
+};
+
+/// Composites the tile state into the frame buffer, one scanline at a time.
+pub struct Ppu {
+    /// Boxed, it's too big to keep on the stack
+    pub frame: Box<FrameBuffer>,
+}
+
+/// A pixel from one of the layers, and how it blends with what's under it
+#[derive(Clone, Copy)]
+struct Pixel {
+    color: Color,
+    blend: BlendMode,
+    /// The layer it's from, blending only happens with layers in [`TileState::blend_targets`]
+    layer: BlendTargets,
+}
+
+#[derive(Clone, Copy)]
+struct BackgroundPixel {
+    pixel: Pixel,
+    background: usize,
+    above_sprites: bool,
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Self {
+            frame: Box::new(FrameBuffer::new()),
+        }
+    }
+
+    pub fn draw_scanline(&mut self, tile_state: &TileState, y: usize) {
+        let mut sprites: Vec<Sprite> = Vec::with_capacity(SPRITE_COUNT);
+        for sprite in tile_state.sprites {
+            if (y as isize) >= sprite.position.1 as isize
+                && (y as isize) < sprite.position.1 as isize + sprite.bounds().1 as isize
+                && sprite.visible
+            {
+                sprites.push(sprite);
+            }
+        }
+        sprites.sort_by_key(|sprite| sprite.position);
+
+        let backdrop = Pixel {
+            color: tile_state.palette[tile_state.background_color as usize],
+            blend: BlendMode::None,
+            layer: BlendTargets::BACKDROP,
+        };
+        for x in 0..WIDTH {
+            // The backgrounds with a pixel here, frontmost first
+            let mut backgrounds =
+                (0..BACKGROUND_COUNT).filter_map(|i| Self::background_pixel(tile_state, i, x, y));
+            let front = backgrounds.next();
+
+            let color = if let Some(sprite) =
+                Self::sprite_pixel(tile_state, &sprites, x, y, |sprite| {
+                    Self::in_front(sprite, front)
+                }) {
+                Self::blend(tile_state, sprite, front.map_or(backdrop, |bg| bg.pixel))
+            } else if let Some(front) = front {
+                if front.pixel.blend == BlendMode::None {
+                    front.pixel.color
+                } else {
+                    // Only worked out when it's needed, it's a second pass over the layers
+                    let next = backgrounds.next();
+                    let below = Self::sprite_pixel(tile_state, &sprites, x, y, |sprite| {
+                        !Self::in_front(sprite, Some(front)) && Self::in_front(sprite, next)
+                    })
+                    .or(next.map(|bg| bg.pixel))
+                    .unwrap_or(backdrop);
+                    Self::blend(tile_state, front.pixel, below)
+                }
+            } else {
+                backdrop.color
+            };
+            self.frame.write_pixel(x, y, color);
+        }
+    }
+
+    fn background_pixel(
+        tile_state: &TileState,
+        background: usize,
+        x: usize,
+        y: usize,
+    ) -> Option<BackgroundPixel> {
+        let bg = &tile_state.backgrounds[background];
+        if !bg.visible {
+            return None;
+        }
+        let (palette_offset, palette) = bg.get_color_offset(tile_state, x, y);
+        (palette_offset != 0).then(|| BackgroundPixel {
+            pixel: Pixel {
+                color: tile_state.get_color(palette, palette_offset),
+                blend: bg.blend,
+                layer: BlendTargets::from_bits_retain(1 << background),
+            },
+            background,
+            above_sprites: bg.is_above_sprites(x, y),
+        })
+    }
+
+    /// The first sprite with a pixel here that `visible` allows
+    fn sprite_pixel(
+        tile_state: &TileState,
+        sprites: &[Sprite],
+        x: usize,
+        y: usize,
+        visible: impl Fn(&Sprite) -> bool,
+    ) -> Option<Pixel> {
+        for sprite in sprites {
+            if (x as isize) >= sprite.position.0 as isize
+                && (x as isize) < sprite.position.0 as isize + sprite.bounds().0 as isize
+                && visible(sprite)
+            {
+                let (palette_offset, palette) = sprite.get_color_offset(tile_state, x, y);
+                if palette_offset != 0 {
+                    return Some(Pixel {
+                        color: tile_state.get_color(palette, palette_offset),
+                        blend: sprite.blend,
+                        layer: BlendTargets::SPRITES,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether a sprite is drawn over a background's pixel, sprites are over nothing at all
+    fn in_front(sprite: &Sprite, bg: Option<BackgroundPixel>) -> bool {
+        bg.is_none_or(|bg| !bg.above_sprites && sprite.priority as usize <= bg.background)
+    }
+
+    fn blend(tile_state: &TileState, top: Pixel, below: Pixel) -> Color {
+        if tile_state.blend_targets.contains(below.layer) {
+            top.blend
+                .apply(top.color, below.color, tile_state.blend_alpha)
+        } else {
+            top.color
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::{Tile, TileAttributes};
+
+    const STRIPES: u8 = 1;
+    const SOLID: u8 = 2;
+
+    /// Background 0 is see-through stripes over a solid background 1, and sprite 0 is solid
+    fn state() -> Box<TileState> {
+        let mut state = Box::new(TileState::new());
+        state.palette[0] = (255, 0, 0).into();
+        state.palette[1] = (0, 255, 0).into();
+        state.palette[2] = (0, 0, 255).into();
+        state.tiles[STRIPES as usize] = Tile([0x10; 128]);
+        state.tiles[SOLID as usize] = Tile([0x11; 128]);
+        state.backgrounds[0].tiles[0] = STRIPES;
+        state.backgrounds[1].tiles[0] = SOLID;
+        state.backgrounds[1].palettes[0] = 1;
+        let sprite = &mut state.sprites[0];
+        sprite.visible = true;
+        sprite.tile = SOLID;
+        sprite.palette = 2;
+        state
+    }
+
+    /// The colors of the first stripe and the gap after it
+    fn draw(state: &TileState) -> (Color, Color) {
+        let mut ppu = Ppu::new();
+        ppu.draw_scanline(state, 0);
+        (ppu.frame.read_pixel(0, 0), ppu.frame.read_pixel(1, 0))
+    }
+
+    #[test]
+    fn sprite_priority_between_backgrounds() {
+        let (red, green, blue) = ((255, 0, 0).into(), (0, 255, 0).into(), (0, 0, 255).into());
+        let mut state = state();
+
+        assert_eq!(draw(&state), (blue, blue));
+        state.sprites[0].priority = 1;
+        assert_eq!(draw(&state), (red, blue));
+        state.sprites[0].priority = 2;
+        assert_eq!(draw(&state), (red, green));
+    }
+
+    #[test]
+    fn priority_cells_cover_front_sprites() {
+        let mut state = state();
+        state.backgrounds[0].tiles[0] = SOLID;
+        assert_eq!(draw(&state).0, (0, 0, 255).into());
+        state.backgrounds[0].attributes[0] = TileAttributes::PRIORITY;
+        assert_eq!(draw(&state).0, (255, 0, 0).into());
+    }
+
+    #[test]
+    fn blends_only_with_targets() {
+        let mut state = state();
+        state.sprites[0].blend = BlendMode::Half;
+        state.sprites[0].priority = 1;
+        // Over background 1 where background 0's stripes are see-through
+        assert_eq!(draw(&state).1, (0, 127, 127).into());
+
+        state.blend_targets = BlendTargets::BACKGROUND_0;
+        assert_eq!(draw(&state).1, (0, 0, 255).into());
+    }
+}
@@ -0,0 +1,156 @@
+use std::{fs, time::SystemTime};
+
+use crate::{
+    console::Console,
+    wasm::{CartError, WasmCart},
+};
+
+/// Watches the cart's file, and reloads it into the console when it changes.
+pub struct HotReload {
+    /// Whether reloading copies the cart's memory into the new instance, instead of calling `init`
+    pub keep_memory: bool,
+    /// When the cart's file was last changed
+    modified: Option<SystemTime>,
+}
+
+impl HotReload {
+    pub fn new(keep_memory: bool) -> Self {
+        Self {
+            keep_memory,
+            modified: None,
+        }
+    }
+
+    /// Whether the cart's file has changed since the last time this was called. The first call
+    /// only notes when it was last changed.
+    pub fn changed(&mut self, cart: &WasmCart) -> bool {
+        let Some(modified) = cart
+            .path()
+            .and_then(|path| fs::metadata(path).ok())
+            .and_then(|metadata| metadata.modified().ok())
+        else {
+            return false;
+        };
+
+        let last = self.modified.replace(modified);
+        last.is_some() && last != Some(modified)
+    }
+
+    /// Loads the cart's file again, keeping the tile state, gamepads and save data. Its memory
+    /// is kept too with [`HotReload::keep_memory`], unless the cart `crashed`, otherwise it's
+    /// started again from `init`.
+    ///
+    /// A cart that can't be loaded is a [`CartError::Load`], and the old one keeps running.
+    pub fn reload(&self, console: &mut Console, crashed: bool) -> Result<(), CartError> {
+        // A crashed cart's memory isn't worth keeping
+        let keep_memory = self.keep_memory && !crashed;
+        console
+            .cart
+            .hot_reload(keep_memory)
+            .map_err(CartError::load)?;
+        if !keep_memory {
+            console.init()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs::File, path::PathBuf, process, time::Duration};
+
+    use super::*;
+    use crate::wasm::CartConfig;
+
+    /// Counts frames in memory, `init` starts counting from `start`
+    fn counter(start: i32) -> String {
+        format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "init") (i32.store (i32.const 0) (i32.const {start})))
+                (func (export "update")
+                    (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (i32.const 1)))))"#
+        )
+    }
+
+    /// A console running a cart written to a file of its own
+    fn console(name: &str) -> (Console, PathBuf) {
+        let dir = env::temp_dir().join(format!("console-reload-{}-{name}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cart.wat");
+        fs::write(&path, counter(0)).unwrap();
+
+        let config = CartConfig {
+            cache_dir: None,
+            ..CartConfig::default()
+        };
+        let mut console = Console::new(WasmCart::load(&path, config).unwrap());
+        console.init().unwrap();
+        console.update().unwrap();
+        console.update().unwrap();
+        (console, path)
+    }
+
+    fn count(console: &mut Console) -> i32 {
+        let cart = &mut console.cart;
+        let memory = cart.save_state().unwrap().memory;
+        i32::from_le_bytes(memory[..4].try_into().unwrap())
+    }
+
+    #[test]
+    fn keeps_memory() {
+        let (mut console, path) = console("keeps_memory");
+        console.cart.state_mut().tile_state.palette[0] = 0x123456.into();
+        fs::write(&path, counter(100)).unwrap();
+
+        HotReload::new(true).reload(&mut console, false).unwrap();
+        assert_eq!(count(&mut console), 2);
+        assert_eq!(console.cart.state().tile_state.palette[0], 0x123456.into());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn discards_memory() {
+        let (mut console, path) = console("discards_memory");
+        fs::write(&path, counter(100)).unwrap();
+
+        HotReload::new(false).reload(&mut console, false).unwrap();
+        assert_eq!(count(&mut console), 100);
+
+        // Nor is it kept for a cart that crashed
+        console.update().unwrap();
+        HotReload::new(true).reload(&mut console, true).unwrap();
+        assert_eq!(count(&mut console), 100);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn broken_carts_keep_the_old_one() {
+        let (mut console, path) = console("broken_carts_keep_the_old_one");
+        fs::write(&path, "(module").unwrap();
+
+        let result = HotReload::new(true).reload(&mut console, false);
+        assert!(matches!(result, Err(CartError::Load { .. })));
+        assert_eq!(count(&mut console), 2);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn notices_changes() {
+        let (console, path) = console("notices_changes");
+        let mut reload = HotReload::new(false);
+        assert!(!reload.changed(&console.cart));
+        assert!(!reload.changed(&console.cart));
+
+        let later = SystemTime::now() + Duration::from_secs(10);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(reload.changed(&console.cart));
+        assert!(!reload.changed(&console.cart));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+
+use crate::savestate::SaveState;
+
+/// Runs of at least this many unchanged bytes end a literal in a delta
+const MIN_ZERO_RUN: usize = 8;
+
+/// The last few seconds of save states, so the cart can be played backwards.
+///
+/// Only the newest snapshot is kept whole. Each older one is stored as the difference from the
+/// snapshot after it, XORed so unchanged bytes become zeros, then with the zeros run-length encoded.
+pub struct RewindBuffer {
+    capacity: usize,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Delta>,
+}
+
+struct Delta {
+    /// The length of the older snapshot, memory can grow between frames
+    len: usize,
+    data: Vec<u8>,
+}
+
+impl RewindBuffer {
+    /// Creates a buffer that can go back `capacity` frames.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            latest: None,
+            deltas: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+    }
+
+    /// Records the state of the current frame.
+    pub fn push(&mut self, state: &SaveState) {
+        let bytes = state.to_bytes();
+        if let Some(previous) = self.latest.replace(bytes) {
+            let latest = self.latest.as_ref().unwrap();
+            self.deltas.push_back(Delta {
+                len: previous.len(),
+                data: encode(&xor(latest, &previous)),
+            });
+        }
+        if self.deltas.len() > self.capacity {
+            self.deltas.pop_front();
+        }
+    }
+
+    /// Steps back a frame, giving the state it had. The current frame is forgotten.
+    pub fn pop(&mut self) -> Option<SaveState> {
+        let delta = self.deltas.pop_back()?;
+        let latest = self.latest.as_ref()?;
+
+        let mut previous = xor(latest, &decode(&delta.data, delta.len));
+        previous.truncate(delta.len);
+
+        let state = SaveState::from_bytes(&previous).ok();
+        self.latest = Some(previous);
+        state
+    }
+}
+
+/// XORs two byte strings, padding the shorter one with zeros
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let len = a.len().max(b.len());
+    (0..len)
+        .map(|i| a.get(i).copied().unwrap_or(0) ^ b.get(i).copied().unwrap_or(0))
+        .collect()
+}
+
+/// Encodes bytes as pairs of a zero run and a literal, each length as a little endian u32
+fn encode(bytes: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let zeros = bytes[i..].iter().take_while(|b| **b == 0).count();
+        i += zeros;
+
+        let start = i;
+        while i < bytes.len() {
+            let run = bytes[i..]
+                .iter()
+                .take(MIN_ZERO_RUN)
+                .take_while(|b| **b == 0)
+                .count();
+            if run == MIN_ZERO_RUN || i + run == bytes.len() {
+                break;
+            }
+            i += run.max(1);
+        }
+
+        out.extend_from_slice(&(zeros as u32).to_le_bytes());
+        out.extend_from_slice(&((i - start) as u32).to_le_bytes());
+        out.extend_from_slice(&bytes[start..i]);
+    }
+    out
+}
+
+fn decode(data: &[u8], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len);
+    let mut data = data;
+    while data.len() >= 8 {
+        let zeros = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
+        let literal = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        out.resize(out.len() + zeros, 0);
+        out.extend_from_slice(&data[8..8 + literal]);
+        data = &data[8 + literal..];
+    }
+    out.resize(out.len().max(len), 0);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gamepad::GamepadState, savestate::GlobalValue, tile::TileState};
+
+    /// A frame's state, with `len` bytes of memory filled with `frame`
+    fn state(frame: u8, len: usize) -> SaveState {
+        SaveState {
+            cart_id: [1; 32],
+            tile_state: Box::new(TileState::new()),
+            gamepads: [GamepadState::Disconnected; 4],
+            memory: vec![frame; len],
+            globals: vec![("frame".to_string(), GlobalValue::I32(frame.into()))],
+        }
+    }
+
+    #[test]
+    fn pops_back_through_frames() {
+        let mut rewind = RewindBuffer::new(8);
+        // Memory grows and gets mostly zeros, like a real cart's
+        let frames = [state(1, 16), state(2, 64), state(0, 64), state(3, 32)];
+        for frame in &frames {
+            rewind.push(frame);
+        }
+        assert_eq!(rewind.len(), 3);
+
+        for expected in frames[..3].iter().rev() {
+            let state = rewind.pop().unwrap();
+            assert_eq!(state.memory, expected.memory);
+            assert_eq!(state.globals, expected.globals);
+        }
+        assert!(rewind.pop().is_none());
+    }
+
+    #[test]
+    fn forgets_past_capacity() {
+        let mut rewind = RewindBuffer::new(2);
+        for frame in 0..5 {
+            rewind.push(&state(frame, 16));
+        }
+        assert_eq!(rewind.len(), 2);
+        assert_eq!(rewind.pop().unwrap().memory, vec![3; 16]);
+        assert_eq!(rewind.pop().unwrap().memory, vec![2; 16]);
+        assert!(rewind.pop().is_none());
+    }
+}
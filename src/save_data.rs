@@ -0,0 +1,190 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{metadata::CartMetadata, wasm::CartId};
+
+/// How many bytes of save data a cart gets
+pub const SAVE_SIZE: usize = 32 * 1024;
+
+/// Battery-backed memory the cart can persist progress in.
+///
+/// It's kept next to the cart, keyed by the cart's identity rather than its file name: its title
+/// and author when it has them, so saves survive updates of the cart, otherwise its hash.
+#[derive(Debug, Clone)]
+pub struct SaveData {
+    pub bytes: Box<[u8; SAVE_SIZE]>,
+    path: Option<PathBuf>,
+    dirty: bool,
+}
+
+impl Default for SaveData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SaveData {
+    /// Save data that only lives in memory
+    pub fn new() -> Self {
+        Self {
+            bytes: Box::new([0; SAVE_SIZE]),
+            path: None,
+            dirty: false,
+        }
+    }
+
+    /// Loads the save data for the cart at `cart_path`, or starts empty if there isn't any yet.
+    pub fn load(cart_path: &Path, metadata: &CartMetadata, id: &CartId) -> io::Result<Self> {
+        let dir = cart_path.parent().unwrap_or(Path::new(""));
+        Self::open(dir.join(Self::file_name(metadata, id)))
+    }
+
+    /// Loads the save data at `path`, or starts empty if there isn't any yet.
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        let mut save_data = Self::new();
+
+        match fs::read(&path) {
+            Ok(bytes) => {
+                let len = bytes.len().min(SAVE_SIZE);
+                save_data.bytes[..len].copy_from_slice(&bytes[..len]);
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+
+        save_data.path = Some(path);
+        Ok(save_data)
+    }
+
+    /// What a cart's save data is called on disk
+    pub fn file_name(metadata: &CartMetadata, id: &CartId) -> String {
+        let key: CartId = match &metadata.title {
+            Some(title) => Sha256::new()
+                .chain_update(title)
+                .chain_update([0])
+                .chain_update(metadata.author.as_deref().unwrap_or_default())
+                .finalize()
+                .into(),
+            None => *id,
+        };
+        let key: String = key.iter().map(|byte| format!("{byte:02x}")).collect();
+        format!("{key}.sav")
+    }
+
+    pub fn write(&mut self, offset: usize, bytes: &[u8]) {
+        self.bytes[offset..offset + bytes.len()].copy_from_slice(bytes);
+        self.dirty = true;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Writes the save data to disk if it's changed. It's written to a temporary file first, then
+    /// moved over the old one, so a crash can't leave it half written.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let temp_path = path.with_extension("sav.tmp");
+        fs::write(&temp_path, &self.bytes[..])?;
+        fs::rename(&temp_path, path)?;
+
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    /// A directory of its own for each test, since they run at the same time
+    fn dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("console-save-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn starts_empty() {
+        let dir = dir("starts_empty");
+        let save_data = SaveData::open(dir.join("cart.sav")).unwrap();
+        assert!(save_data.bytes.iter().all(|&byte| byte == 0));
+        assert!(!save_data.is_dirty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn writes_are_flushed_and_read_back() {
+        let dir = dir("writes_are_flushed_and_read_back");
+        let path = dir.join("cart.sav");
+        let mut save_data = SaveData::open(path.clone()).unwrap();
+
+        // Nothing's written until something changes
+        save_data.flush().unwrap();
+        assert!(!path.exists());
+
+        save_data.write(10, &[1, 2, 3]);
+        assert!(save_data.is_dirty());
+        save_data.flush().unwrap();
+        assert!(!save_data.is_dirty());
+        assert!(!path.with_extension("sav.tmp").exists());
+
+        let read = SaveData::open(path).unwrap();
+        assert_eq!(read.bytes[..], save_data.bytes[..]);
+        assert_eq!(read.bytes[9..14], [0, 1, 2, 3, 0]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn flushing_replaces_leftover_temp_files() {
+        let dir = dir("flushing_replaces_leftover_temp_files");
+        let path = dir.join("cart.sav");
+        fs::write(&path, [7; 4]).unwrap();
+        // From a flush that didn't finish
+        fs::write(path.with_extension("sav.tmp"), [9; 2]).unwrap();
+
+        let mut save_data = SaveData::open(path.clone()).unwrap();
+        assert_eq!(save_data.bytes[..5], [7, 7, 7, 7, 0]);
+        save_data.write(0, &[1]);
+        save_data.flush().unwrap();
+
+        let written = fs::read(&path).unwrap();
+        assert_eq!(written.len(), SAVE_SIZE);
+        assert_eq!(written[..5], [1, 7, 7, 7, 0]);
+        assert!(!path.with_extension("sav.tmp").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn named_after_the_cart() {
+        let titled = |title: &str, author: Option<&str>| CartMetadata {
+            title: Some(title.to_string()),
+            author: author.map(str::to_string),
+            ..CartMetadata::default()
+        };
+        let name = |metadata: &CartMetadata, id: u8| SaveData::file_name(metadata, &[id; 32]);
+
+        // Titled carts keep their saves when they change
+        let game = titled("Game", Some("Someone"));
+        assert_eq!(name(&game, 1), name(&game, 2));
+        assert_ne!(name(&game, 1), name(&titled("Game", None), 1));
+        assert_ne!(name(&game, 1), name(&titled("Game 2", Some("Someone")), 1));
+
+        let untitled = CartMetadata::default();
+        assert_eq!(name(&untitled, 1), format!("{}.sav", "01".repeat(32)));
+        assert_ne!(name(&untitled, 1), name(&untitled, 2));
+    }
+}
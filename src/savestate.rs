@@ -0,0 +1,397 @@
+use std::{fs, path::Path};
+
+use wasmtime::{Val, V128};
+
+use crate::{
+    color::Color,
+    gamepad::GamepadState,
+    math::Fixed,
+    tile::{
+        BlendMode, BlendTargets, Sprite, Tile, TileAttributes, TileMap, TileState,
+        BACKGROUND_COUNT, SPRITE_MATRIX_COUNT, SPRITE_SIZES,
+    },
+    wasm::CartId,
+};
+
+const MAGIC: &[u8; 4] = b"FCSS";
+/// Bumped whenever the layout changes, older save states can't be loaded
+pub const VERSION: u32 = 6;
+
+/// A snapshot of the whole machine, see [`crate::wasm::WasmCart::save_state`]
+#[derive(Debug, Clone)]
+pub struct SaveState {
+    pub cart_id: CartId,
+    pub tile_state: Box<TileState>,
+    pub gamepads: [GamepadState; 4],
+    pub memory: Vec<u8>,
+    /// Exported mutable globals, by name
+    pub globals: Vec<(String, GlobalValue)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlobalValue {
+    I32(i32),
+    I64(i64),
+    /// The bits of an f32
+    F32(u32),
+    /// The bits of an f64
+    F64(u64),
+    V128(u128),
+}
+
+/// Reads little endian values, erroring if `bytes` runs out
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    /// What's being read, for error messages
+    what: &'static str,
+}
+
+impl SaveState {
+    pub fn read(path: impl AsRef<Path>) -> wasmtime::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> wasmtime::Result<()> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.memory.len() + 128 * 1024);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&self.cart_id);
+
+        for gamepad in self.gamepads {
+            out.extend_from_slice(&gamepad.bits().to_le_bytes());
+        }
+
+        write_tile_state(&mut out, &self.tile_state);
+
+        out.extend_from_slice(&(self.globals.len() as u32).to_le_bytes());
+        for (name, value) in &self.globals {
+            out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+            let (tag, bits) = match *value {
+                GlobalValue::I32(value) => (0, value as u32 as u128),
+                GlobalValue::I64(value) => (1, value as u64 as u128),
+                GlobalValue::F32(bits) => (2, bits as u128),
+                GlobalValue::F64(bits) => (3, bits as u128),
+                GlobalValue::V128(bits) => (4, bits),
+            };
+            out.push(tag);
+            out.extend_from_slice(&bits.to_le_bytes());
+        }
+
+        out.extend_from_slice(&(self.memory.len() as u64).to_le_bytes());
+        out.extend_from_slice(&self.memory);
+
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> wasmtime::Result<Self> {
+        let mut reader = Reader::new(bytes, "save state");
+
+        if reader.take(4)? != MAGIC {
+            return Err(wasmtime::Error::msg("not a save state"));
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(wasmtime::Error::msg(format!(
+                "save state is version {version}, expected {VERSION}"
+            )));
+        }
+        let cart_id = reader.take(32)?.try_into()?;
+
+        let mut gamepads = [GamepadState::Disconnected; 4];
+        for gamepad in &mut gamepads {
+            *gamepad = GamepadState::from_bits_retain(reader.u16()?);
+        }
+
+        let tile_state = read_tile_state(&mut reader)?;
+
+        let mut globals = vec![];
+        for _ in 0..reader.u32()? {
+            let name = reader.string()?;
+            let tag = reader.u8()?;
+            let bits = u128::from_le_bytes(reader.take(16)?.try_into()?);
+            let value = match tag {
+                0 => GlobalValue::I32(bits as u32 as i32),
+                1 => GlobalValue::I64(bits as u64 as i64),
+                2 => GlobalValue::F32(bits as u32),
+                3 => GlobalValue::F64(bits as u64),
+                4 => GlobalValue::V128(bits),
+                _ => return Err(wasmtime::Error::msg(format!("unknown global type {tag}"))),
+            };
+            globals.push((name, value));
+        }
+
+        let len = reader.u64()? as usize;
+        let memory = reader.take(len)?.to_vec();
+
+        Ok(Self {
+            cart_id,
+            tile_state,
+            gamepads,
+            memory,
+            globals,
+        })
+    }
+}
+
+fn write_tile_state(out: &mut Vec<u8>, tile_state: &TileState) {
+    for color in tile_state.palette {
+        out.extend_from_slice(&[color.r, color.g, color.b]);
+    }
+    for tile in &tile_state.tiles {
+        out.extend_from_slice(&tile.0);
+    }
+    out.push(tile_state.background_color);
+
+    for bg in &tile_state.backgrounds {
+        out.extend_from_slice(&bg.palettes);
+        out.extend_from_slice(&bg.tiles);
+        out.extend(bg.attributes.iter().map(|attributes| attributes.bits()));
+        for value in [
+            bg.pre_offset.0,
+            bg.pre_offset.1,
+            bg.post_offset.0,
+            bg.post_offset.1,
+            bg.matrix.0 .0.raw(),
+            bg.matrix.0 .1.raw(),
+            bg.matrix.1 .0.raw(),
+            bg.matrix.1 .1.raw(),
+        ] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out.push(bg.visible as u8);
+        out.push(bg.blend as u8);
+    }
+
+    for sprite in &tile_state.sprites {
+        out.extend_from_slice(&[
+            sprite.visible as u8,
+            sprite.flip_x as u8,
+            sprite.flip_y as u8,
+            sprite.palette,
+            sprite.tile,
+            sprite.priority,
+            sprite.size.0,
+            sprite.size.1,
+            sprite.matrix.unwrap_or(u8::MAX),
+            sprite.double_size as u8,
+            sprite.blend as u8,
+        ]);
+        out.extend_from_slice(&sprite.position.0.to_le_bytes());
+        out.extend_from_slice(&sprite.position.1.to_le_bytes());
+    }
+
+    for matrix in &tile_state.sprite_matrices {
+        for value in [matrix.0 .0, matrix.0 .1, matrix.1 .0, matrix.1 .1] {
+            out.extend_from_slice(&value.raw().to_le_bytes());
+        }
+    }
+
+    out.extend_from_slice(&tile_state.blend_targets.bits().to_le_bytes());
+    out.extend_from_slice(&[tile_state.blend_alpha.0, tile_state.blend_alpha.1]);
+}
+
+fn read_tile_state(reader: &mut Reader) -> wasmtime::Result<Box<TileState>> {
+    let mut tile_state = Box::new(TileState::new());
+
+    for color in &mut tile_state.palette {
+        let [r, g, b] = reader.take(3)?.try_into()?;
+        *color = Color { r, g, b };
+    }
+    for tile in &mut tile_state.tiles {
+        *tile = Tile(reader.take(128)?.try_into()?);
+    }
+    tile_state.background_color = reader.u8()?;
+
+    for bg in &mut tile_state.backgrounds {
+        *bg = TileMap {
+            palettes: reader.take(4096)?.try_into()?,
+            tiles: reader.take(4096)?.try_into()?,
+            attributes: <[u8; 4096]>::try_from(reader.take(4096)?)?
+                .map(TileAttributes::from_bits_retain),
+            pre_offset: (reader.i16()?, reader.i16()?),
+            post_offset: (reader.i16()?, reader.i16()?),
+            matrix: (
+                (Fixed::from(reader.i16()?), Fixed::from(reader.i16()?)),
+                (Fixed::from(reader.i16()?), Fixed::from(reader.i16()?)),
+            ),
+            visible: reader.u8()? != 0,
+            blend: read_blend_mode(reader)?,
+        };
+    }
+
+    for sprite in &mut tile_state.sprites {
+        *sprite = Sprite {
+            visible: reader.u8()? != 0,
+            flip_x: reader.u8()? != 0,
+            flip_y: reader.u8()? != 0,
+            palette: reader.u8()?,
+            tile: reader.u8()?,
+            priority: reader.u8()?,
+            size: (reader.u8()?, reader.u8()?),
+            matrix: Some(reader.u8()?).filter(|&matrix| matrix != u8::MAX),
+            double_size: reader.u8()? != 0,
+            blend: read_blend_mode(reader)?,
+            position: (reader.i16()?, reader.i16()?),
+        };
+        if let Some(matrix) = sprite.matrix
+            && matrix as usize >= SPRITE_MATRIX_COUNT
+        {
+            return Err(wasmtime::Error::msg(format!(
+                "sprite matrix {matrix} is out of range"
+            )));
+        }
+        let (width, height) = sprite.size;
+        if !SPRITE_SIZES.contains(&width) || !SPRITE_SIZES.contains(&height) {
+            return Err(wasmtime::Error::msg(format!(
+                "sprite size {width}x{height} isn't one of {SPRITE_SIZES:?}"
+            )));
+        }
+        if sprite.priority as usize > BACKGROUND_COUNT {
+            return Err(wasmtime::Error::msg(format!(
+                "sprite priority {} is out of range",
+                sprite.priority
+            )));
+        }
+    }
+
+    for matrix in &mut tile_state.sprite_matrices {
+        *matrix = (
+            (Fixed::from(reader.i16()?), Fixed::from(reader.i16()?)),
+            (Fixed::from(reader.i16()?), Fixed::from(reader.i16()?)),
+        );
+    }
+
+    tile_state.blend_targets = BlendTargets::from_bits_retain(reader.u16()?);
+    tile_state.blend_alpha = (reader.u8()?, reader.u8()?);
+
+    Ok(tile_state)
+}
+
+fn read_blend_mode(reader: &mut Reader) -> wasmtime::Result<BlendMode> {
+    let mode = reader.u8()?;
+    BlendMode::from_u8(mode)
+        .ok_or_else(|| wasmtime::Error::msg(format!("unknown blend mode {mode}")))
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8], what: &'static str) -> Self {
+        Self { bytes, what }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> wasmtime::Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(wasmtime::Error::msg(format!("{} is truncated", self.what)));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub(crate) fn u8(&mut self) -> wasmtime::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> wasmtime::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    pub(crate) fn i16(&mut self) -> wasmtime::Result<i16> {
+        Ok(i16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> wasmtime::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> wasmtime::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    /// A string prefixed with its length as a u32
+    pub(crate) fn string(&mut self) -> wasmtime::Result<String> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+}
+
+impl TryFrom<Val> for GlobalValue {
+    type Error = wasmtime::Error;
+
+    fn try_from(value: Val) -> Result<Self, Self::Error> {
+        match value {
+            Val::I32(value) => Ok(Self::I32(value)),
+            Val::I64(value) => Ok(Self::I64(value)),
+            Val::F32(bits) => Ok(Self::F32(bits)),
+            Val::F64(bits) => Ok(Self::F64(bits)),
+            Val::V128(bits) => Ok(Self::V128(bits.as_u128())),
+            _ => Err(wasmtime::Error::msg(
+                "reference globals can't be saved in a save state",
+            )),
+        }
+    }
+}
+
+impl From<GlobalValue> for Val {
+    fn from(value: GlobalValue) -> Self {
+        match value {
+            GlobalValue::I32(value) => Val::I32(value),
+            GlobalValue::I64(value) => Val::I64(value),
+            GlobalValue::F32(bits) => Val::F32(bits),
+            GlobalValue::F64(bits) => Val::F64(bits),
+            GlobalValue::V128(bits) => Val::V128(V128::from(bits)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> SaveState {
+        SaveState {
+            cart_id: [1; 32],
+            tile_state: Box::new(TileState::new()),
+            gamepads: [GamepadState::Disconnected; 4],
+            memory: vec![1, 2, 3],
+            globals: vec![("count".to_string(), GlobalValue::I64(-4))],
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut saved = state();
+        saved.tile_state.sprites[3].size = (64, 16);
+        saved.tile_state.sprites[3].matrix = Some(2);
+        let read = SaveState::from_bytes(&saved.to_bytes()).unwrap();
+        assert_eq!(read.cart_id, saved.cart_id);
+        assert_eq!(read.memory, saved.memory);
+        assert_eq!(read.globals, saved.globals);
+        assert_eq!(read.tile_state.sprites[3].size, (64, 16));
+        assert_eq!(read.tile_state.sprites[3].matrix, Some(2));
+    }
+
+    #[test]
+    fn rejects_out_of_range_sprites() {
+        let mut size = state();
+        size.tile_state.sprites[0].size = (0, 8);
+        assert!(SaveState::from_bytes(&size.to_bytes()).is_err());
+
+        let mut priority = state();
+        priority.tile_state.sprites[0].priority = BACKGROUND_COUNT as u8 + 1;
+        assert!(SaveState::from_bytes(&priority.to_bytes()).is_err());
+
+        let mut matrix = state();
+        matrix.tile_state.sprites[0].matrix = Some(SPRITE_MATRIX_COUNT as u8);
+        assert!(SaveState::from_bytes(&matrix.to_bytes()).is_err());
+    }
+}
@@ -0,0 +1,47 @@
+use bitflags::bitflags;
+
+use crate::wasm::CartConfig;
+
+/// The version of the API the console gives carts, bumped whenever something's added to it.
+/// Carts built against an older version keep working.
+pub const API_VERSION: u32 = 7;
+
+bitflags! {
+    /// Optional parts of the API, a cart can check these before relying on them
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Features: u32 {
+        /// The `save` module
+        const SAVE_DATA = 1;
+        /// The `asset` module, for carts loaded from a package
+        const ASSETS = 2;
+        /// The `draw`, `on_pause`, `on_resume`, `on_reset` and `on_exit` exports are called
+        const HOOKS = 4;
+        /// The `wasi_snapshot_preview1` module, for carts built against wasi-libc or Rust's
+        /// `wasm32-wasip1` target
+        const WASI = 8;
+    }
+}
+
+impl Features {
+    /// What a cart gets with the config
+    pub fn for_config(config: &CartConfig) -> Self {
+        let mut features = Self::SAVE_DATA | Self::ASSETS | Self::HOOKS;
+        features.set(Self::WASI, config.wasi);
+        features
+    }
+}
+
+/// Checks the API version a cart asked for in its metadata.
+pub fn check_api_version(requested: u32) -> wasmtime::Result<()> {
+    if requested == 0 {
+        return Err(wasmtime::Error::msg(
+            "api_version 0 doesn't exist, the first is 1",
+        ));
+    }
+    if requested > API_VERSION {
+        return Err(wasmtime::Error::msg(format!(
+            "cart needs API version {requested}, but this console only supports up to {API_VERSION}"
+        )));
+    }
+    Ok(())
+}
@@ -1,21 +1,73 @@
-use std::sync::{Mutex, MutexGuard, OnceLock};
+use bitflags::bitflags;
 
 use crate::{color::Color, math::Fixed};
 
-static STATE: OnceLock<Mutex<TileState>> = OnceLock::new();
+pub const PALETTE_SIZE: usize = 256;
+pub const TILE_COUNT: usize = 256;
+pub const BACKGROUND_COUNT: usize = 8;
+pub const SPRITE_COUNT: usize = 64;
+/// The widths and heights a sprite can have, in pixels
+pub const SPRITE_SIZES: [u8; 4] = [8, 16, 32, 64];
+/// How many matrices affine sprites can share
+pub const SPRITE_MATRIX_COUNT: usize = 32;
 
 /// 1 nibble per pixel, 16*16 pixels, 128 bytes
 #[derive(Debug, Clone, Copy)]
 pub struct Tile(pub [u8; 128]);
 
+bitflags! {
+    /// Settings for each cell of a [`TileMap`]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct TileAttributes: u8 {
+        const FLIP_X = 1;
+        const FLIP_Y = 2;
+        /// Drawn over sprites, whatever their priority
+        const PRIORITY = 4;
+    }
+}
+
+/// How a background or sprite's pixels blend with what's under them, see
+/// [`TileState::blend_targets`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    None,
+    /// Mixed by [`TileState::blend_alpha`]
+    Alpha,
+    Add,
+    /// What's under it subtracted from it
+    Subtract,
+    /// The average of the two
+    Half,
+}
+
+bitflags! {
+    /// The layers a blending pixel can blend with, whatever's under it otherwise isn't blended
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct BlendTargets: u16 {
+        const BACKGROUND_0 = 1;
+        const BACKGROUND_1 = 2;
+        const BACKGROUND_2 = 4;
+        const BACKGROUND_3 = 8;
+        const BACKGROUND_4 = 16;
+        const BACKGROUND_5 = 32;
+        const BACKGROUND_6 = 64;
+        const BACKGROUND_7 = 128;
+        const SPRITES = 256;
+        /// The background color
+        const BACKDROP = 512;
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct TileMap {
     pub palettes: [u8; 4096],
     pub tiles: [u8; 4096],
+    pub attributes: [TileAttributes; 4096],
     pub pre_offset: (i16, i16),
     pub post_offset: (i16, i16),
     pub matrix: ((Fixed, Fixed), (Fixed, Fixed)),
     pub visible: bool,
+    pub blend: BlendMode,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -26,15 +78,33 @@ pub struct Sprite {
     pub palette: u8,
     pub tile: u8,
     pub position: (i16, i16),
+    /// How many background layers are drawn over the sprite, from the first. 0 puts it in front
+    /// of all of them, [`BACKGROUND_COUNT`] behind all of them.
+    pub priority: u8,
+    /// Width and height in pixels, one of [`SPRITE_SIZES`] each. Sprites bigger than a tile
+    /// are made of consecutive tiles, a row at a time.
+    pub size: (u8, u8),
+    /// Which of [`TileState::sprite_matrices`] rotates and scales the sprite, if any. Affine
+    /// sprites ignore their flips, the matrix can mirror them instead.
+    pub matrix: Option<u8>,
+    /// Draws an affine sprite in a box twice its size, so its corners aren't cut off when it's
+    /// rotated or scaled up
+    pub double_size: bool,
+    pub blend: BlendMode,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct TileState {
-    pub palette: [Color; 256],
-    pub tiles: [Tile; 256],
+    pub palette: [Color; PALETTE_SIZE],
+    pub tiles: [Tile; TILE_COUNT],
     pub background_color: u8,
-    pub backgrounds: [TileMap; 8],
-    pub sprites: [Sprite; 64],
+    pub backgrounds: [TileMap; BACKGROUND_COUNT],
+    pub sprites: [Sprite; SPRITE_COUNT],
+    /// Shared by affine sprites, each transforms the screen into the sprite around its center
+    pub sprite_matrices: [((Fixed, Fixed), (Fixed, Fixed)); SPRITE_MATRIX_COUNT],
+    pub blend_targets: BlendTargets,
+    /// How much of a pixel and what's under it [`BlendMode::Alpha`] takes, in 16ths
+    pub blend_alpha: (u8, u8),
 }
 
 impl Tile {
@@ -48,15 +118,54 @@ impl Tile {
     }
 }
 
+impl BlendMode {
+    pub const ALL: [BlendMode; 5] = [
+        Self::None,
+        Self::Alpha,
+        Self::Add,
+        Self::Subtract,
+        Self::Half,
+    ];
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Self::ALL.get(value as usize).copied()
+    }
+
+    /// Blends `top` over `bottom`, `alpha` is [`TileState::blend_alpha`]
+    pub fn apply(self, top: Color, bottom: Color, alpha: (u8, u8)) -> Color {
+        let channel = |top: u8, bottom: u8| {
+            let (top, bottom) = (top as u32, bottom as u32);
+            let value = match self {
+                Self::None => top,
+                Self::Alpha => (top * alpha.0 as u32 + bottom * alpha.1 as u32) / 16,
+                Self::Add => top + bottom,
+                Self::Subtract => top.saturating_sub(bottom),
+                Self::Half => (top + bottom) / 2,
+            };
+            value.min(255) as u8
+        };
+        Color {
+            r: channel(top.r, bottom.r),
+            g: channel(top.g, bottom.g),
+            b: channel(top.b, bottom.b),
+        }
+    }
+}
+
 impl TileMap {
     pub fn get_color_offset(&self, tile_state: &TileState, px: usize, py: usize) -> (u8, u8) {
         let tile_loc = self.get_tile_index(px, py);
         let tile_idx = self.tiles[tile_loc];
         let tile = tile_state.tiles[tile_idx as usize];
-        let tile_offset = self.get_tile_offset(px, py);
+        let tile_offset = self.get_tile_offset(px, py, self.attributes[tile_loc]);
         (tile.get_color(tile_offset), self.palettes[tile_loc])
     }
 
+    /// Whether the cell at a pixel is drawn over sprites
+    pub fn is_above_sprites(&self, px: usize, py: usize) -> bool {
+        self.attributes[self.get_tile_index(px, py)].contains(TileAttributes::PRIORITY)
+    }
+
     fn transform_coords(&self, px: isize, py: isize) -> (usize, usize) {
         let tx = px + self.pre_offset.0 as isize;
         let ty = py + self.pre_offset.1 as isize;
@@ -88,12 +197,18 @@ impl TileMap {
         x | y
     }
 
-    fn get_tile_offset(&self, px: usize, py: usize) -> usize {
+    fn get_tile_offset(&self, px: usize, py: usize, attributes: TileAttributes) -> usize {
         let (mut x, mut y) = self.transform_coords(px as isize, py as isize);
 
         x %= 16;
+        if attributes.contains(TileAttributes::FLIP_X) {
+            x = 15 - x;
+        }
 
         y %= 16;
+        if attributes.contains(TileAttributes::FLIP_Y) {
+            y = 15 - y;
+        }
         y *= 16;
 
         x | y
@@ -102,55 +217,105 @@ impl TileMap {
 
 impl Sprite {
     pub fn get_color_offset(&self, tile_state: &TileState, px: usize, py: usize) -> (u8, u8) {
-        let tile = tile_state.tiles[self.tile as usize];
-        let tile_offset = self.get_tile_offset(px, py);
-        (tile.get_color(tile_offset), self.palette)
+        let coords = match self.matrix {
+            Some(matrix) => {
+                self.get_affine_coords(tile_state.sprite_matrices[matrix as usize], px, py)
+            }
+            None => Some(self.get_sprite_coords(px, py)),
+        };
+        let Some((x, y)) = coords else {
+            return (0, self.palette);
+        };
+        let columns = (self.size.0 as usize).div_ceil(16);
+        let tile_idx = self.tile.wrapping_add((y / 16 * columns + x / 16) as u8);
+        let tile = tile_state.tiles[tile_idx as usize];
+        (tile.get_color(x % 16 + y % 16 * 16), self.palette)
     }
 
-    fn get_tile_offset(&self, px: usize, py: usize) -> usize {
+    pub fn width(&self) -> usize {
+        self.size.0 as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.size.1 as usize
+    }
+
+    /// The size of the box the sprite's drawn in, twice its size for double size affine sprites
+    pub fn bounds(&self) -> (usize, usize) {
+        if self.matrix.is_some() && self.double_size {
+            (self.width() * 2, self.height() * 2)
+        } else {
+            (self.width(), self.height())
+        }
+    }
+
+    /// Where a pixel on the screen is within an affine sprite, `None` if it's outside of it
+    fn get_affine_coords(
+        &self,
+        matrix: ((Fixed, Fixed), (Fixed, Fixed)),
+        px: usize,
+        py: usize,
+    ) -> Option<(usize, usize)> {
+        let (bounds_x, bounds_y) = self.bounds();
+        let cx = px as isize - self.position.0 as isize - bounds_x as isize / 2;
+        let cy = py as isize - self.position.1 as isize - bounds_y as isize / 2;
+
+        let x = cx * matrix.0 .0 + cy * matrix.0 .1 + self.width() as isize / 2;
+        let y = cx * matrix.1 .0 + cy * matrix.1 .1 + self.height() as isize / 2;
+
+        ((0..self.width() as isize).contains(&x) && (0..self.height() as isize).contains(&y))
+            .then_some((x as usize, y as usize))
+    }
+
+    /// Where a pixel on the screen is within the sprite, after flipping it
+    fn get_sprite_coords(&self, px: usize, py: usize) -> (usize, usize) {
         let mut x = px.wrapping_sub(self.position.0 as usize);
         let mut y = py.wrapping_sub(self.position.1 as usize);
 
-        x %= 16;
-
+        x %= self.width();
         if self.flip_x {
-            x = 16 - x;
+            x = self.width() - 1 - x;
         }
 
-        y %= 16;
+        y %= self.height();
         if self.flip_y {
-            y = 16 - y;
+            y = self.height() - 1 - y;
         }
-        y *= 16;
 
-        x | y
+        (x, y)
+    }
+}
+
+impl Default for TileState {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl TileState {
-    pub fn get() -> MutexGuard<'static, Self> {
-        STATE
-            .get_or_init(|| Mutex::new(TileState::new()))
-            .lock()
-            .unwrap()
+    /// Gets the color for a nonzero pixel value in a tile, palettes wrap around at the end.
+    pub fn get_color(&self, palette: u8, palette_offset: u8) -> Color {
+        self.palette[(palette as usize + palette_offset as usize - 1) % PALETTE_SIZE]
     }
 
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             background_color: 0,
-            palette: [(0, 0, 0).into(); 256],
-            tiles: [Tile([0; 128]); 256],
+            palette: [(0, 0, 0).into(); PALETTE_SIZE],
+            tiles: [Tile([0; 128]); TILE_COUNT],
             backgrounds: [TileMap {
                 palettes: [0; 4096],
                 tiles: [0; 4096],
+                attributes: [TileAttributes::empty(); 4096],
                 pre_offset: (0, 0),
                 post_offset: (0, 0),
                 matrix: (
                     (Fixed::from(256), Fixed::from(0)),
                     (Fixed::from(0), Fixed::from(256)),
                 ),
-                visible: true
-            }; 8],
+                visible: true,
+                blend: BlendMode::None,
+            }; BACKGROUND_COUNT],
             sprites: [Sprite {
                 visible: false,
                 flip_x: false,
@@ -158,7 +323,155 @@ impl TileState {
                 palette: 0,
                 tile: 0,
                 position: (0, 0),
-            }; 64],
+                priority: 0,
+                size: (16, 16),
+                matrix: None,
+                double_size: false,
+                blend: BlendMode::None,
+            }; SPRITE_COUNT],
+            sprite_matrices: [(
+                (Fixed::from(256), Fixed::from(0)),
+                (Fixed::from(0), Fixed::from(256)),
+            ); SPRITE_MATRIX_COUNT],
+            blend_targets: BlendTargets::all(),
+            blend_alpha: (8, 8),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flipped_tile_offsets() {
+        let state = Box::new(TileState::new());
+        // Pixel (1, 2) of the cell at (1, 0)
+        let offset = |attributes| state.backgrounds[0].get_tile_offset(17, 2, attributes);
+        assert_eq!(offset(TileAttributes::empty()), 1 + 2 * 16);
+        assert_eq!(offset(TileAttributes::FLIP_X), 14 + 2 * 16);
+        assert_eq!(offset(TileAttributes::FLIP_Y), 1 + 13 * 16);
+        assert_eq!(offset(TileAttributes::all()), 14 + 13 * 16);
+    }
+
+    #[test]
+    fn priority_cells_are_above_sprites() {
+        let mut state = Box::new(TileState::new());
+        let bg = &mut state.backgrounds[0];
+        bg.attributes[1] = TileAttributes::PRIORITY;
+        assert!(!bg.is_above_sprites(15, 15));
+        assert!(bg.is_above_sprites(16, 0));
+        assert!(bg.is_above_sprites(31, 15));
+        assert!(!bg.is_above_sprites(32, 0));
+    }
+
+    /// What every pixel of a tile is in these tests, so the tile a pixel comes from is visible
+    fn offset(tile: u8) -> u8 {
+        tile % 13 + 1
+    }
+
+    fn sprite_state(tile: u8, size: (u8, u8)) -> Box<TileState> {
+        let mut state = Box::new(TileState::new());
+        for (i, tile) in state.tiles.iter_mut().enumerate() {
+            *tile = Tile([offset(i as u8) * 0x11; 128]);
         }
+        state.sprites[0].tile = tile;
+        state.sprites[0].size = size;
+        state
+    }
+
+    #[test]
+    fn wide_sprites_take_tiles_a_row_at_a_time() {
+        let state = sprite_state(1, (32, 16));
+        let sprite = state.sprites[0];
+        assert_eq!(sprite.get_color_offset(&state, 0, 0).0, offset(1));
+        assert_eq!(sprite.get_color_offset(&state, 31, 15).0, offset(2));
+    }
+
+    #[test]
+    fn big_sprites_take_tiles_a_row_at_a_time() {
+        let state = sprite_state(1, (64, 64));
+        let sprite = state.sprites[0];
+        assert_eq!(sprite.get_color_offset(&state, 0, 0).0, offset(1));
+        assert_eq!(sprite.get_color_offset(&state, 48, 16).0, offset(8));
+        assert_eq!(sprite.get_color_offset(&state, 0, 48).0, offset(13));
+        assert_eq!(sprite.get_color_offset(&state, 63, 63).0, offset(16));
+    }
+
+    #[test]
+    fn sprite_tiles_wrap_past_the_last() {
+        let state = sprite_state(255, (32, 16));
+        let sprite = state.sprites[0];
+        assert_eq!(sprite.get_color_offset(&state, 0, 0).0, offset(255));
+        assert_eq!(sprite.get_color_offset(&state, 16, 0).0, offset(0));
+    }
+
+    fn matrix(a: i16, b: i16, c: i16, d: i16) -> ((Fixed, Fixed), (Fixed, Fixed)) {
+        (
+            (Fixed::from(a), Fixed::from(b)),
+            (Fixed::from(c), Fixed::from(d)),
+        )
+    }
+
+    #[test]
+    fn affine_identity() {
+        let sprite = TileState::new().sprites[0];
+        let identity = matrix(256, 0, 0, 256);
+        assert_eq!(sprite.get_affine_coords(identity, 0, 0), Some((0, 0)));
+        assert_eq!(sprite.get_affine_coords(identity, 3, 5), Some((3, 5)));
+        assert_eq!(sprite.get_affine_coords(identity, 15, 15), Some((15, 15)));
+    }
+
+    #[test]
+    fn affine_rotation() {
+        let sprite = TileState::new().sprites[0];
+        // A quarter turn around the middle of the sprite
+        let rotation = matrix(0, 256, -256, 0);
+        assert_eq!(sprite.get_affine_coords(rotation, 3, 5), Some((5, 13)));
+        assert_eq!(sprite.get_affine_coords(rotation, 8, 8), Some((8, 8)));
+    }
+
+    #[test]
+    fn affine_outside_the_sprite() {
+        let sprite = TileState::new().sprites[0];
+        let identity = matrix(256, 0, 0, 256);
+        assert_eq!(sprite.get_affine_coords(identity, 16, 0), None);
+        // At 45 degrees the corners of the box are outside of the sprite
+        let rotation = matrix(181, 181, -181, 181);
+        assert_eq!(sprite.get_affine_coords(rotation, 0, 0), None);
+        assert_eq!(sprite.get_affine_coords(rotation, 8, 8), Some((8, 8)));
+    }
+
+    #[test]
+    fn affine_double_size() {
+        let mut sprite = TileState::new().sprites[0];
+        sprite.matrix = Some(0);
+        sprite.double_size = true;
+        assert_eq!(sprite.bounds(), (32, 32));
+
+        // The sprite is in the middle of its box
+        let identity = matrix(256, 0, 0, 256);
+        assert_eq!(sprite.get_affine_coords(identity, 4, 4), None);
+        assert_eq!(sprite.get_affine_coords(identity, 8, 8), Some((0, 0)));
+        assert_eq!(sprite.get_affine_coords(identity, 23, 23), Some((15, 15)));
+        assert_eq!(sprite.get_affine_coords(identity, 24, 24), None);
+        // Scaled down by half it fills the box
+        let half = matrix(128, 0, 0, 128);
+        assert_eq!(sprite.get_affine_coords(half, 0, 0), Some((0, 0)));
+        assert_eq!(sprite.get_affine_coords(half, 31, 31), Some((15, 15)));
+    }
+
+    #[test]
+    fn blend_modes() {
+        let color = |r, g, b| Color { r, g, b };
+        let apply =
+            |mode: BlendMode, alpha| mode.apply(color(200, 100, 0), color(100, 200, 40), alpha);
+        assert_eq!(apply(BlendMode::None, (8, 8)), color(200, 100, 0));
+        assert_eq!(apply(BlendMode::Alpha, (8, 8)), color(150, 150, 20));
+        assert_eq!(apply(BlendMode::Alpha, (12, 4)), color(175, 125, 10));
+        assert_eq!(apply(BlendMode::Alpha, (16, 16)), color(255, 255, 40));
+        assert_eq!(apply(BlendMode::Add, (8, 8)), color(255, 255, 40));
+        assert_eq!(apply(BlendMode::Subtract, (8, 8)), color(100, 0, 0));
+        assert_eq!(apply(BlendMode::Half, (8, 8)), color(150, 150, 20));
     }
 }
@@ -1,154 +1,1088 @@
 use std::{
-    ops::DerefMut,
+    any::Any,
+    ffi::CStr,
+    fmt::Display,
+    fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
     stringify,
-    sync::{Mutex, OnceLock},
+    sync::Arc,
 };
 
-use wasmtime::{AsContextMut, Engine, Instance, Linker, Memory, Module, Store, TypedFunc};
+use sha2::{Digest, Sha256};
+use wasmtime::{
+    Caller, Config, Engine, Extern, Global, Instance, Linker, Memory, Module, Mutability, Store,
+    StoreLimits, StoreLimitsBuilder, Trap, TypedFunc, WasmBacktrace, WasmParams, WasmResults,
+};
+
+use self::wasi::{CartExit, WasiState};
+use crate::{
+    abi::{AbiFunction, AbiModule, AbiParam, AbiType},
+    gamepad::GamepadState,
+    metadata::CartMetadata,
+    package::{Assets, CartPackage, PackageMetadata},
+    save_data::SaveData,
+    savestate::SaveState,
+    system::{Features, API_VERSION},
+    tile::{BlendMode, BlendTargets, TileState},
+};
+
+/// Just enough of WASI preview1 for carts built against wasi-libc or Rust's `wasm32-wasip1`
+/// target. Output goes to the debug console, and there's no filesystem, arguments or environment.
+mod wasi;
+
+/// Compiled carts, so they only need compiling once for each engine configuration.
+pub mod cache;
+
+/// The size of a page of wasm memory
+const PAGE_SIZE: usize = 65536;
+
+/// Identifies a cart by the SHA-256 hash of the file it was loaded from
+pub type CartId = [u8; 32];
+
+/// Everything the host functions can see, owned by the cart's store.
+pub struct HostState {
+    pub tile_state: Box<TileState>,
+    pub gamepads: [GamepadState; 4],
+    pub save_data: SaveData,
+    pub assets: Arc<Assets>,
+    pub strict: bool,
+    pub features: Features,
+    limits: StoreLimits,
+    wasi: WasiState,
+}
+
+#[derive(Debug, Clone)]
+pub struct CartConfig {
+    /// Trap when the cart passes an out of range index or pointer to a host function,
+    /// instead of ignoring the call
+    pub strict: bool,
+    /// How much the cart can run each frame, or `None` to let it run for as long as it likes
+    pub budget: Option<CpuBudget>,
+    /// The console's RAM, how big the cart's memory can grow in bytes. Past this, `memory.grow`
+    /// returns -1.
+    pub memory_limit: usize,
+    /// How many elements each of the cart's tables can have
+    pub table_limit: u32,
+    /// Give the cart the `wasi_snapshot_preview1` module, see [`Features::WASI`]
+    pub wasi: bool,
+    /// Where compiled carts are cached, or `None` to compile them every time
+    pub cache_dir: Option<PathBuf>,
+}
+
+/// Limits on how much work the cart can do, measured in wasmtime fuel.
+/// Fuel roughly counts wasm instructions.
+#[derive(Debug, Clone, Copy)]
+pub struct CpuBudget {
+    /// Fuel for each call to `init`, `update`, `draw` and the event hooks
+    pub frame: u64,
+    /// Fuel for each call to `v_blank`
+    pub scanline: u64,
+    /// How many times over its budget a call can go before it's stopped with a trap,
+    /// regardless of the overrun policy
+    pub hard_limit: u64,
+    pub overrun: OverrunPolicy,
+}
+
+/// What happens when a call goes over its budget, but stays under the hard limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverrunPolicy {
+    /// Skip drawing the frame when `update` overruns, leaving the last one on screen
+    DropFrame,
+    /// Keep going, but show a slowdown indicator
+    Slowdown,
+    /// Stop the cart with a [`CartError::Overrun`]
+    Trap,
+}
+
+/// How much fuel the cart used during the last frame
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuMetrics {
+    pub update: u64,
+    pub draw: u64,
+    pub v_blank_total: u64,
+    pub v_blank_max: u64,
+    /// Whether `update` went over the frame budget
+    pub update_overrun: bool,
+    /// Whether `draw` went over the frame budget
+    pub draw_overrun: bool,
+    /// How many calls to `v_blank` went over the scanline budget
+    pub v_blank_overruns: u32,
+}
 
-static CART: OnceLock<WasmCart> = OnceLock::new();
 pub struct WasmCart {
-    store: Mutex<Store<()>>,
+    config: CartConfig,
+    id: CartId,
+    path: Option<PathBuf>,
+    metadata: CartMetadata,
+    /// Set when the cart was loaded from a package
+    package: Option<PackageMetadata>,
+    module: Module,
+    linker: Linker<HostState>,
+    store: Store<HostState>,
     instance: Instance,
-    init: TypedFunc<(), ()>,
-    update: TypedFunc<(), ()>,
-    v_blank: TypedFunc<u32, ()>,
+    exports: CartExports,
+    metrics: CpuMetrics,
 }
 
-impl WasmCart {
-    pub fn load(file_path: &str) -> wasmtime::Result<()> {
-        let cart = Self::new(file_path)?;
-        CART.get_or_init(move || cart);
-        Ok(())
+/// The functions the cart can export, all of them are optional
+struct CartExports {
+    /// Runs a WASI reactor's constructors, called before `init`
+    initialize: Option<TypedFunc<(), ()>>,
+    init: Option<TypedFunc<(), ()>>,
+    update: Option<TypedFunc<(), ()>>,
+    /// Called after `update`, before the scanlines are drawn
+    draw: Option<TypedFunc<(), ()>>,
+    v_blank: Option<TypedFunc<u32, ()>>,
+    on_pause: Option<TypedFunc<(), ()>>,
+    on_resume: Option<TypedFunc<(), ()>>,
+    /// Called before the cart is reset
+    on_reset: Option<TypedFunc<(), ()>>,
+    /// Called before the console exits
+    on_exit: Option<TypedFunc<(), ()>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum CartError {
+    /// The cart trapped, or a host function it called returned an error
+    Trap {
+        export: &'static str,
+        trap: Option<Trap>,
+        message: String,
+        backtrace: Option<String>,
+    },
+    /// A host function panicked while the cart was running
+    Panic {
+        export: &'static str,
+        message: String,
+    },
+    /// The cart went over its budget, with [`OverrunPolicy::Trap`]
+    Overrun {
+        export: &'static str,
+        used: u64,
+        budget: u64,
+    },
+    /// The cart called WASI's `proc_exit`
+    Exit { export: &'static str, code: u32 },
+    /// The cart couldn't be loaded or instantiated
+    Load { message: String },
+}
+
+impl HostState {
+    pub fn new(config: &CartConfig) -> Self {
+        Self {
+            tile_state: Box::new(TileState::new()),
+            gamepads: [GamepadState::Disconnected; 4],
+            save_data: SaveData::new(),
+            assets: Arc::default(),
+            strict: config.strict,
+            features: Features::for_config(config),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(config.memory_limit)
+                .table_elements(config.table_limit)
+                .build(),
+            wasi: WasiState::new(),
+        }
     }
+}
 
-    pub fn init() {
-        let cart = Self::get();
-        let mut store = cart.store.lock().unwrap();
-        cart.init.call(store.deref_mut(), ()).unwrap();
+impl Default for CartConfig {
+    fn default() -> Self {
+        Self {
+            strict: cfg!(debug_assertions),
+            budget: Some(CpuBudget::default()),
+            memory_limit: 16 * 1024 * 1024,
+            table_limit: 10_000,
+            wasi: true,
+            cache_dir: cache::default_dir(),
+        }
     }
+}
 
-    pub fn update() {
-        let cart = Self::get();
-        let mut store = cart.store.lock().unwrap();
-        cart.update.call(store.deref_mut(), ()).unwrap();
+impl Default for CpuBudget {
+    fn default() -> Self {
+        Self {
+            frame: 5_000_000,
+            scanline: 20_000,
+            hard_limit: 20,
+            overrun: OverrunPolicy::Slowdown,
+        }
     }
+}
 
-    pub fn v_blank(y: u32) {
-        let cart = Self::get();
-        let mut store = cart.store.lock().unwrap();
-        cart.v_blank.call(store.deref_mut(), y).unwrap();
+impl CpuMetrics {
+    pub fn overrun(&self) -> bool {
+        self.update_overrun || self.draw_overrun || self.v_blank_overruns > 0
     }
+}
 
-    fn get() -> &'static Self {
-        &CART.get().unwrap()
+impl WasmCart {
+    /// Loads a cart from a package, `.wasm` or `.wat` file, along with its save data.
+    pub fn load(file_path: impl AsRef<Path>, config: CartConfig) -> wasmtime::Result<Self> {
+        let bytes = fs::read(file_path.as_ref())?;
+        let mut cart = if CartPackage::is_package(&bytes) {
+            Self::from_bytes(&bytes, config)?
+        } else {
+            let engine = Self::engine(&config)?;
+            let module = cache::compile(&engine, &bytes, config.cache_dir.as_deref())?;
+            Self::new(
+                engine,
+                module,
+                &bytes,
+                Sha256::digest(&bytes).into(),
+                config,
+            )?
+        };
+        cart.store.data_mut().save_data =
+            SaveData::load(file_path.as_ref(), &cart.metadata, &cart.id)?;
+        cart.path = Some(file_path.as_ref().to_owned());
+        Ok(cart)
     }
 
-    fn new(file_path: &str) -> wasmtime::Result<Self> {
-        let engine = Engine::default();
-        let module = Module::from_file(&engine, file_path)?;
+    /// Loads a cart from the bytes of a package or a compiled wasm module.
+    pub fn from_bytes(bytes: &[u8], config: CartConfig) -> wasmtime::Result<Self> {
+        let id = Sha256::digest(bytes).into();
+        let engine = Self::engine(&config)?;
 
-        let mut store: Store<()> = Store::new(&engine, ());
+        if !CartPackage::is_package(bytes) {
+            let module = cache::compile(&engine, bytes, config.cache_dir.as_deref())?;
+            return Self::new(engine, module, bytes, id, config);
+        }
 
-        let mut linker = Linker::<()>::new(&engine);
+        let package = CartPackage::from_bytes(bytes)?;
+        let module = cache::compile(&engine, &package.wasm, config.cache_dir.as_deref())?;
+        let mut cart = Self::new(engine, module, &package.wasm, id, config)?;
+        cart.store.data_mut().assets = Arc::new(package.assets);
+        cart.metadata.merge_package(&package.metadata);
+        cart.package = Some(package.metadata);
+        Ok(cart)
+    }
 
-        func!(
-            linker,
-            tile::write_palette,
-            tile::write_tile,
-            tile::set_background_tile,
-            tile::set_background_palette,
-            tile::set_background_tile_palette,
-            tile::set_background_visible,
-            tile::set_background_scroll_post,
-            tile::set_background_scroll_post_x,
-            tile::set_background_scroll_post_y,
-            tile::get_background_scroll_post,
-            tile::get_background_scroll_post_x,
-            tile::get_background_scroll_post_y,
-            tile::set_background_scroll_pre,
-            tile::set_background_scroll_pre_x,
-            tile::set_background_scroll_pre_y,
-            tile::get_background_scroll_pre,
-            tile::get_background_scroll_pre_x,
-            tile::get_background_scroll_pre_y,
-            tile::set_background_transformation_matrix,
-            sprite::set_sprite_palette,
-            sprite::set_sprite_tile,
-            sprite::set_sprite_visible,
-            sprite::get_sprite_position,
-            sprite::get_sprite_position_x,
-            sprite::get_sprite_position_y,
-            sprite::set_sprite_position,
-            sprite::set_sprite_flip,
-            dbg::write_character,
-            dbg::write_str,
-            dbg::write_int,
-            dbg::write_uint,
-            dbg::write_ptr,
-            dbg::end_line,
-            gamepad::get_state
-        );
+    /// Loads a cart from wasm text format.
+    pub fn from_wat(wat: &str, config: CartConfig) -> wasmtime::Result<Self> {
+        let engine = Self::engine(&config)?;
+        let module = Module::new(&engine, wat)?;
+        Self::new(
+            engine,
+            module,
+            wat.as_bytes(),
+            Sha256::digest(wat).into(),
+            config,
+        )
+    }
 
-        let instance = linker.instantiate(&mut store, &module)?;
+    /// `source` is what the module was compiled from, its metadata is read from there.
+    fn new(
+        engine: Engine,
+        module: Module,
+        source: &[u8],
+        id: CartId,
+        config: CartConfig,
+    ) -> wasmtime::Result<Self> {
+        let metadata = CartMetadata::from_wasm(source)?;
+        if let Some(api_version) = metadata.api_version {
+            crate::system::check_api_version(api_version)?;
+        }
+
+        let mut linker = Linker::<HostState>::new(&engine);
+
+        add_host_functions(&mut linker)?;
+        if config.wasi {
+            wasi::add_to_linker(&mut linker)?;
+        }
 
-        let init = instance.get_typed_func::<(), ()>(&mut store, "init")?;
-        let update = instance.get_typed_func::<(), ()>(&mut store, "update")?;
-        let v_blank = instance.get_typed_func::<u32, ()>(&mut store, "v_blank")?;
+        let (store, instance, exports) = Self::instantiate(&engine, &linker, &module, &config)?;
 
         Ok(Self {
-            store: Mutex::new(store),
+            config,
+            id,
+            path: None,
+            metadata,
+            package: None,
+            module,
+            linker,
+            store,
             instance,
-            init,
-            update,
-            v_blank,
+            exports,
+            metrics: CpuMetrics::default(),
+        })
+    }
+
+    fn engine(config: &CartConfig) -> wasmtime::Result<Engine> {
+        Engine::new(Config::new().consume_fuel(config.budget.is_some()))
+    }
+
+    fn instantiate(
+        engine: &Engine,
+        linker: &Linker<HostState>,
+        module: &Module,
+        config: &CartConfig,
+    ) -> wasmtime::Result<(Store<HostState>, Instance, CartExports)> {
+        let mut store = Store::new(engine, HostState::new(config));
+        store.limiter(|state| &mut state.limits);
+
+        let unknown: Vec<String> = module
+            .imports()
+            .filter(|import| linker.get_by_import(&mut store, import).is_none())
+            .map(|import| format!("`{}::{}`", import.module(), import.name()))
+            .collect();
+        if !unknown.is_empty() {
+            return Err(wasmtime::Error::msg(format!(
+                "cart imports {}, which this console doesn't have (it supports API version {})",
+                unknown.join(", "),
+                API_VERSION
+            )));
+        }
+
+        let instance = linker.instantiate(&mut store, module)?;
+
+        let exports = CartExports {
+            initialize: Self::export(&instance, &mut store, "_initialize")?,
+            init: Self::export(&instance, &mut store, "init")?,
+            update: Self::export(&instance, &mut store, "update")?,
+            draw: Self::export(&instance, &mut store, "draw")?,
+            v_blank: Self::export(&instance, &mut store, "v_blank")?,
+            on_pause: Self::export(&instance, &mut store, "on_pause")?,
+            on_resume: Self::export(&instance, &mut store, "on_resume")?,
+            on_reset: Self::export(&instance, &mut store, "on_reset")?,
+            on_exit: Self::export(&instance, &mut store, "on_exit")?,
+        };
+
+        Ok((store, instance, exports))
+    }
+
+    /// Gets an optional exported function, which has to have the right type if it's there.
+    fn export<Params: WasmParams, Results: WasmResults>(
+        instance: &Instance,
+        store: &mut Store<HostState>,
+        name: &str,
+    ) -> wasmtime::Result<Option<TypedFunc<Params, Results>>> {
+        match instance.get_func(&mut *store, name) {
+            Some(func) => {
+                Ok(Some(func.typed(&*store).map_err(|err| {
+                    err.context(format!("`{name}` has the wrong type"))
+                })?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn config(&self) -> &CartConfig {
+        &self.config
+    }
+
+    pub fn id(&self) -> &CartId {
+        &self.id
+    }
+
+    /// The file the cart was loaded from, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn metadata(&self) -> &CartMetadata {
+        &self.metadata
+    }
+
+    /// The package's metadata, if the cart was loaded from one.
+    pub fn package(&self) -> Option<&PackageMetadata> {
+        self.package.as_ref()
+    }
+
+    /// Throws away the instance and everything in the store except the save data and assets,
+    /// then instantiates the cart again.
+    ///
+    /// `init` has to be called again afterwards.
+    pub fn reset(&mut self) -> Result<(), CartError> {
+        let (mut store, instance, exports) = Self::instantiate(
+            self.store.engine(),
+            &self.linker,
+            &self.module,
+            &self.config,
+        )
+        .map_err(CartError::load)?;
+        store.data_mut().save_data = self.store.data().save_data.clone();
+        store.data_mut().assets = self.store.data().assets.clone();
+        self.store = store;
+        self.instance = instance;
+        self.exports = exports;
+        Ok(())
+    }
+
+    /// Loads the cart's file again, keeping the tile state, gamepads and save data, and if
+    /// `keep_memory` is set, copying the old instance's memory into the new one.
+    ///
+    /// Without `keep_memory`, `init` has to be called again afterwards.
+    pub fn hot_reload(&mut self, keep_memory: bool) -> wasmtime::Result<()> {
+        let path = self
+            .path
+            .clone()
+            .ok_or_else(|| wasmtime::Error::msg("cart wasn't loaded from a file"))?;
+        let mut cart = Self::load(path, self.config.clone())?;
+
+        let state = self.store.data();
+        let new_state = cart.store.data_mut();
+        new_state.tile_state = state.tile_state.clone();
+        new_state.gamepads = state.gamepads;
+        new_state.save_data = state.save_data.clone();
+
+        if keep_memory
+            && let Some(old_memory) = self.memory()
+            && let Some(memory) = cart.memory()
+        {
+            let old_data = old_memory.data(&self.store);
+            let current = memory.data_size(&cart.store);
+            if current < old_data.len() {
+                memory.grow(
+                    &mut cart.store,
+                    ((old_data.len() - current) / PAGE_SIZE) as u64,
+                )?;
+            }
+            memory.data_mut(&mut cart.store)[..old_data.len()].copy_from_slice(old_data);
+        }
+
+        *self = cart;
+        Ok(())
+    }
+
+    /// Takes a snapshot of everything the cart can see: the tile state, the gamepads,
+    /// its memory, and its exported mutable globals.
+    pub fn save_state(&mut self) -> wasmtime::Result<SaveState> {
+        let memory = match self.memory() {
+            Some(memory) => memory.data(&self.store).to_vec(),
+            None => vec![],
+        };
+
+        let mut globals = vec![];
+        for (name, global) in self.mutable_globals() {
+            globals.push((name, global.get(&mut self.store).try_into()?));
+        }
+
+        let state = self.store.data();
+        Ok(SaveState {
+            cart_id: self.id,
+            tile_state: state.tile_state.clone(),
+            gamepads: state.gamepads,
+            memory,
+            globals,
         })
     }
 
-    fn get_memory(&self, store: impl AsContextMut) -> Memory {
-        self.instance.get_memory(store, "memory").unwrap()
+    /// Restores a snapshot from [`WasmCart::save_state`], which has to be from the same cart.
+    pub fn load_state(&mut self, state: &SaveState) -> wasmtime::Result<()> {
+        if state.cart_id != self.id {
+            return Err(wasmtime::Error::msg("save state is for a different cart"));
+        }
+
+        if let Some(mut memory) = self.memory() {
+            // Memory can't shrink, so start over with a fresh instance if it's grown since
+            if memory.data_size(&self.store) > state.memory.len() {
+                self.reset()?;
+                memory = self
+                    .memory()
+                    .ok_or_else(|| wasmtime::Error::msg("cart doesn't export its memory"))?;
+            }
+
+            let current = memory.data_size(&self.store);
+            if current < state.memory.len() {
+                memory.grow(
+                    &mut self.store,
+                    ((state.memory.len() - current) / PAGE_SIZE) as u64,
+                )?;
+            }
+            memory.data_mut(&mut self.store)[..state.memory.len()].copy_from_slice(&state.memory);
+        }
+
+        for (name, value) in &state.globals {
+            let global = self
+                .instance
+                .get_global(&mut self.store, name)
+                .ok_or_else(|| wasmtime::Error::msg(format!("cart has no global `{name}`")))?;
+            global.set(&mut self.store, (*value).into())?;
+        }
+
+        let host = self.store.data_mut();
+        host.tile_state = state.tile_state.clone();
+        host.gamepads = state.gamepads;
+        Ok(())
+    }
+
+    fn memory(&mut self) -> Option<Memory> {
+        self.instance.get_memory(&mut self.store, "memory")
+    }
+
+    /// How many bytes of memory the cart is using.
+    pub fn memory_size(&mut self) -> usize {
+        match self.memory() {
+            Some(memory) => memory.data_size(&self.store),
+            None => 0,
+        }
+    }
+
+    fn mutable_globals(&mut self) -> Vec<(String, Global)> {
+        let globals: Vec<(String, Global)> = self
+            .instance
+            .exports(&mut self.store)
+            .filter_map(|export| {
+                let name = export.name().to_string();
+                export.into_global().map(|global| (name, global))
+            })
+            .collect();
+
+        globals
+            .into_iter()
+            .filter(|(_, global)| global.ty(&self.store).mutability() == Mutability::Var)
+            .collect()
+    }
+
+    pub fn init(&mut self) -> Result<(), CartError> {
+        let budget = self.config.budget.map(|budget| budget.frame);
+        self.call(self.exports.initialize.clone(), "_initialize", (), budget)?;
+        self.call(self.exports.init.clone(), "init", (), budget)?;
+        Ok(())
+    }
+
+    pub fn on_pause(&mut self) -> Result<(), CartError> {
+        self.call_hook(self.exports.on_pause.clone(), "on_pause")
+    }
+
+    pub fn on_resume(&mut self) -> Result<(), CartError> {
+        self.call_hook(self.exports.on_resume.clone(), "on_resume")
+    }
+
+    pub fn on_reset(&mut self) -> Result<(), CartError> {
+        self.call_hook(self.exports.on_reset.clone(), "on_reset")
+    }
+
+    pub fn on_exit(&mut self) -> Result<(), CartError> {
+        self.call_hook(self.exports.on_exit.clone(), "on_exit")
+    }
+
+    /// Calls a hook for a host event, which gets a frame's worth of fuel.
+    fn call_hook(
+        &mut self,
+        func: Option<TypedFunc<(), ()>>,
+        export: &'static str,
+    ) -> Result<(), CartError> {
+        let budget = self.config.budget.map(|budget| budget.frame);
+        self.call(func, export, (), budget)?;
+        Ok(())
+    }
+
+    /// Runs the cart's update, which starts a new frame for the metrics.
+    pub fn update(&mut self) -> Result<(), CartError> {
+        self.metrics = CpuMetrics::default();
+        let budget = self.config.budget.map(|budget| budget.frame);
+        let ((), used) = self.call(self.exports.update.clone(), "update", (), budget)?;
+        self.metrics.update = used;
+        self.metrics.update_overrun = budget.is_some_and(|budget| used > budget);
+        Ok(())
+    }
+
+    pub fn draw(&mut self) -> Result<(), CartError> {
+        let budget = self.config.budget.map(|budget| budget.frame);
+        let ((), used) = self.call(self.exports.draw.clone(), "draw", (), budget)?;
+        self.metrics.draw = used;
+        self.metrics.draw_overrun = budget.is_some_and(|budget| used > budget);
+        Ok(())
+    }
+
+    pub fn v_blank(&mut self, y: u32) -> Result<(), CartError> {
+        let budget = self.config.budget.map(|budget| budget.scanline);
+        let ((), used) = self.call(self.exports.v_blank.clone(), "v_blank", y, budget)?;
+        self.metrics.v_blank_total += used;
+        self.metrics.v_blank_max = self.metrics.v_blank_max.max(used);
+        if budget.is_some_and(|budget| used > budget) {
+            self.metrics.v_blank_overruns += 1;
+        }
+        Ok(())
+    }
+
+    /// Writes the save data to disk, if the cart has written to it since the last flush.
+    pub fn flush_save_data(&mut self) -> io::Result<()> {
+        self.store.data_mut().save_data.flush()
+    }
+
+    pub fn metrics(&self) -> &CpuMetrics {
+        &self.metrics
+    }
+
+    pub fn state(&self) -> &HostState {
+        self.store.data()
+    }
+
+    pub fn state_mut(&mut self) -> &mut HostState {
+        self.store.data_mut()
+    }
+
+    /// Calls into the cart, returning how much fuel it used. Functions the cart doesn't export
+    /// do nothing.
+    fn call<Params: WasmParams, Results: WasmResults + Default>(
+        &mut self,
+        func: Option<TypedFunc<Params, Results>>,
+        export: &'static str,
+        params: Params,
+        budget: Option<u64>,
+    ) -> Result<(Results, u64), CartError> {
+        let Some(func) = func else {
+            return Ok((Results::default(), 0));
+        };
+
+        let limit = self.config.budget.zip(budget).map(|(config, budget)| {
+            let limit = budget.saturating_mul(config.hard_limit);
+            self.store
+                .set_fuel(limit)
+                .expect("fuel is enabled when there's a budget");
+            limit
+        });
+
+        // A panicking host function shouldn't take the whole console down with the cart
+        let results =
+            match panic::catch_unwind(AssertUnwindSafe(|| func.call(&mut self.store, params))) {
+                Ok(Ok(results)) => results,
+                Ok(Err(err)) => return Err(CartError::trap(export, err)),
+                Err(payload) => return Err(CartError::panic(export, payload)),
+            };
+
+        let Some(limit) = limit else {
+            return Ok((results, 0));
+        };
+        let used = limit - self.store.get_fuel().unwrap_or(0);
+
+        if let (Some(config), Some(budget)) = (self.config.budget, budget)
+            && config.overrun == OverrunPolicy::Trap
+            && used > budget
+        {
+            return Err(CartError::Overrun {
+                export,
+                used,
+                budget,
+            });
+        }
+
+        Ok((results, used))
+    }
+}
+
+impl CartError {
+    pub fn load(err: wasmtime::Error) -> Self {
+        Self::Load {
+            message: format!("{err:#}"),
+        }
+    }
+
+    fn trap(export: &'static str, err: wasmtime::Error) -> Self {
+        if let Some(CartExit(code)) = err.downcast_ref::<CartExit>() {
+            return Self::Exit {
+                export,
+                code: *code,
+            };
+        }
+        Self::Trap {
+            export,
+            trap: err.downcast_ref::<Trap>().copied(),
+            message: err.root_cause().to_string(),
+            backtrace: err
+                .downcast_ref::<WasmBacktrace>()
+                .map(|backtrace| backtrace.to_string()),
+        }
+    }
+
+    fn panic(export: &'static str, payload: Box<dyn Any + Send>) -> Self {
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown panic".to_string()
+        };
+        Self::Panic { export, message }
+    }
+
+    pub fn backtrace(&self) -> Option<&str> {
+        match self {
+            Self::Trap { backtrace, .. } => backtrace.as_deref(),
+            _ => None,
+        }
     }
 }
 
-macro func($linker: expr, $($module: ident :: $f: ident),+) {
-    $(
-        $linker.func_wrap(stringify!($module), stringify!($f), $module::$f)?;
-    )+
+impl Display for CartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Trap {
+                export, message, ..
+            } => write!(f, "`{export}` trapped: {message}"),
+            Self::Panic { export, message } => write!(f, "`{export}` panicked: {message}"),
+            Self::Overrun {
+                export,
+                used,
+                budget,
+            } => write!(
+                f,
+                "`{export}` used {used} fuel, over its budget of {budget}"
+            ),
+            Self::Exit { export, code } => write!(f, "`{export}` exited with code {code}"),
+            Self::Load { message } => write!(f, "couldn't load cart: {message}"),
+        }
+    }
 }
 
-mod gamepad {
-    use crate::gamepad::GamepadStateManager;
+impl std::error::Error for CartError {}
 
-    pub fn get_state(gamepad_idx: u32) -> u32 {
-        GamepadStateManager::get().gamepads[gamepad_idx as usize]
-            .state
-            .bits() as u32
+fn get_memory(caller: &mut Caller<HostState>) -> wasmtime::Result<Memory> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| wasmtime::Error::msg("cart doesn't export its memory"))
+}
+
+// Every index, value and pointer a cart passes to a host function is checked before it's used.
+// In strict mode anything out of range traps, with a message saying what was wrong. Otherwise
+// setters that get one do nothing, and getters return their empty value (0, or a disconnected
+// gamepad). Coordinates within a background map aren't indices, they wrap around the map.
+
+fn checked<'a, T>(
+    strict: bool,
+    items: &'a [T],
+    idx: u32,
+    name: &str,
+) -> wasmtime::Result<Option<&'a T>> {
+    match items.get(idx as usize) {
+        Some(item) => Ok(Some(item)),
+        None => out_of_range(strict, name, idx, items.len()),
     }
 }
 
-mod dbg {
-    use std::ffi::CStr;
+fn checked_mut<'a, T>(
+    strict: bool,
+    items: &'a mut [T],
+    idx: u32,
+    name: &str,
+) -> wasmtime::Result<Option<&'a mut T>> {
+    let len = items.len();
+    match items.get_mut(idx as usize) {
+        Some(item) => Ok(Some(item)),
+        None => out_of_range(strict, name, idx, len),
+    }
+}
 
-    use wasmtime::Caller;
+fn checked_value(strict: bool, value: u32, len: usize, name: &str) -> wasmtime::Result<Option<u8>> {
+    if (value as usize) < len {
+        Ok(Some(value as u8))
+    } else {
+        out_of_range(strict, name, value, len)
+    }
+}
 
-    use super::WasmCart;
+fn checked_blend_mode(strict: bool, mode: u32) -> wasmtime::Result<Option<BlendMode>> {
+    match u8::try_from(mode).ok().and_then(BlendMode::from_u8) {
+        Some(mode) => Ok(Some(mode)),
+        None => out_of_range(strict, "blend mode", mode, BlendMode::ALL.len()),
+    }
+}
 
-    pub fn write_character(c: u32) {
-        print!("{}", char::from_u32(c).unwrap());
+fn checked_blend_targets(strict: bool, targets: u32) -> wasmtime::Result<Option<BlendTargets>> {
+    match u16::try_from(targets)
+        .ok()
+        .and_then(BlendTargets::from_bits)
+    {
+        Some(targets) => Ok(Some(targets)),
+        None if strict => Err(wasmtime::Error::msg(format!(
+            "blend targets {targets:#x} aren't a combination of {:#x}",
+            BlendTargets::all().bits()
+        ))),
+        None => Ok(None),
     }
+}
 
-    pub fn write_str(mut caller: Caller<()>, s: u32) {
-        let cart = WasmCart::get();
-        let mem = cart.get_memory(&mut caller);
+fn checked_memory(
+    strict: bool,
+    memory: &[u8],
+    ptr: u32,
+    len: usize,
+) -> wasmtime::Result<Option<&[u8]>> {
+    match memory.get(ptr as usize..ptr as usize + len) {
+        Some(bytes) => Ok(Some(bytes)),
+        None if strict => Err(wasmtime::Error::msg(format!(
+            "{len} bytes at {ptr:#x} are out of bounds, memory is {} bytes",
+            memory.len()
+        ))),
+        None => Ok(None),
+    }
+}
 
-        unsafe {
-            let p = mem.data_ptr(caller).offset(s as isize) as *const i8;
+fn checked_memory_mut(
+    strict: bool,
+    memory: &mut [u8],
+    ptr: u32,
+    len: usize,
+) -> wasmtime::Result<Option<&mut [u8]>> {
+    let memory_len = memory.len();
+    match memory.get_mut(ptr as usize..ptr as usize + len) {
+        Some(bytes) => Ok(Some(bytes)),
+        None if strict => Err(wasmtime::Error::msg(format!(
+            "{len} bytes at {ptr:#x} are out of bounds, memory is {memory_len} bytes"
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// Gets the nul terminated string at `ptr`
+fn checked_c_str(strict: bool, memory: &[u8], ptr: u32) -> wasmtime::Result<Option<&CStr>> {
+    let Some(bytes) = memory.get(ptr as usize..) else {
+        if strict {
+            return Err(wasmtime::Error::msg(format!(
+                "string at {ptr:#x} is out of bounds, memory is {} bytes",
+                memory.len()
+            )));
+        }
+        return Ok(None);
+    };
 
-            let cs = CStr::from_ptr(p);
+    match CStr::from_bytes_until_nul(bytes) {
+        Ok(s) => Ok(Some(s)),
+        Err(err) if strict => Err(err.into()),
+        Err(_) => Ok(None),
+    }
+}
 
-            print!("{}", cs.to_str().unwrap());
+fn out_of_range<T>(strict: bool, name: &str, idx: u32, len: usize) -> wasmtime::Result<Option<T>> {
+    if strict {
+        Err(wasmtime::Error::msg(format!(
+            "{name} {idx} is out of range, expected less than {len}"
+        )))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Describes the host API once, generating both [`HOST_ABI`] and `add_host_functions`, which adds
+/// the functions to a linker. Each function's C name and parameter types are for the generated
+/// bindings, the Rust functions are in the module of the same name.
+macro_rules! host_abi {
+    ($(
+        $(#[doc = $module_doc: literal])*
+        mod $module: ident {
+            $(
+                $(#[doc = $doc: literal])*
+                fn $f: ident($($param: ident: $ty: ident),*) $(-> $ret: ident)? = $c_name: ident;
+            )*
+        }
+    )*) => {
+        /// Every function the console gives carts, see [`crate::abi`]
+        pub const HOST_ABI: &[AbiModule] = &[$(
+            AbiModule {
+                name: stringify!($module),
+                doc: &[$($module_doc),*],
+                functions: &[$(
+                    AbiFunction {
+                        name: stringify!($f),
+                        c_name: stringify!($c_name),
+                        doc: &[$($doc),*],
+                        params: &[$(AbiParam { name: stringify!($param), ty: AbiType::$ty }),*],
+                        results: &[$(AbiType::$ret)?],
+                    },
+                )*],
+            },
+        )*];
+
+        fn add_host_functions(linker: &mut Linker<HostState>) -> wasmtime::Result<()> {
+            $($(
+                linker.func_wrap(stringify!($module), stringify!($f), $module::$f)?;
+            )*)*
+            Ok(())
+        }
+    };
+}
+
+host_abi! {
+    mod tile {
+        fn write_palette(palette: U8, color: U32) = tile_WritePalette;
+        fn write_tile(tile_number: U8, tile: ConstBytes) = tile_WriteTile;
+        fn set_background_tile(background: U8, pos: MapPosition, tile: U8) = tile_Set_BackgroundTile;
+        fn set_background_visible(background: U8, visible: Bool) = tile_Set_BackgroundVisible;
+        /// Sets how the background's pixels blend with what's under them
+        fn set_background_blend(background: U8, mode: BlendMode) = tile_Set_BackgroundBlend;
+        /// Which layers, as BLEND_TARGET_ flags, blending pixels blend with. Pixels over any
+        /// other layer are drawn as they are.
+        fn set_blend_targets(targets: U32) = tile_Set_BlendTargets;
+        /// How much of a pixel and what's under it Blend_Alpha takes, each out of 16
+        fn set_blend_alpha(top: U8, below: U8) = tile_Set_BlendAlpha;
+        /// Sets the palette entry drawn where nothing else is
+        fn set_background_palette(palette: U8) = tile_Set_BackgroundPalette;
+        fn set_background_tile_palette(background: U8, pos: MapPosition, palette: U8) = tile_Set_BackgroundTilePalette;
+        /// Mirrors the tile at a position in a background
+        fn set_background_tile_flip(background: U8, pos: MapPosition, flip_x: Bool, flip_y: Bool) = tile_Set_BackgroundTileFlip;
+        /// Draws the tile at a position in a background over sprites, whatever their priority
+        fn set_background_tile_priority(background: U8, pos: MapPosition, above_sprites: Bool) = tile_Set_BackgroundTilePriority;
+        fn set_background_scroll_pre(background: U8, pos: Position) = tile_Set_BackgroundScroll_Pre;
+        fn set_background_scroll_pre_x(background: U8, x: I16) = tile_Set_BackgroundScroll_Pre_X;
+        fn set_background_scroll_pre_y(background: U8, y: I16) = tile_Set_BackgroundScroll_Pre_Y;
+        fn get_background_scroll_pre(background: U8) -> Position = tile_Get_BackgroundScroll_Pre;
+        fn get_background_scroll_pre_x(background: U8) -> I16 = tile_Get_BackgroundScroll_Pre_X;
+        fn get_background_scroll_pre_y(background: U8) -> I16 = tile_Get_BackgroundScroll_Pre_Y;
+        fn set_background_scroll_post(background: U8, pos: Position) = tile_Set_BackgroundScroll_Post;
+        fn set_background_scroll_post_x(background: U8, x: I16) = tile_Set_BackgroundScroll_Post_X;
+        fn set_background_scroll_post_y(background: U8, y: I16) = tile_Set_BackgroundScroll_Post_Y;
+        fn get_background_scroll_post(background: U8) -> Position = tile_Get_BackgroundScroll_Post;
+        fn get_background_scroll_post_x(background: U8) -> I16 = tile_Get_BackgroundScroll_Post_X;
+        fn get_background_scroll_post_y(background: U8) -> I16 = tile_Get_BackgroundScroll_Post_Y;
+        fn set_background_transformation_matrix(background: U8, a: I16, b: I16, c: I16, d: I16) = tile_Set_BackgroundMatrix;
+    }
+
+    mod sprite {
+        fn set_sprite_tile(sprite: U8, tile: U8) = sprite_Set_Tile;
+        fn set_sprite_visible(sprite: U8, visible: Bool) = sprite_Set_Visible;
+        fn set_sprite_palette(sprite: U8, palette: U8) = sprite_Set_Palette;
+        fn get_sprite_position(sprite: U8) -> Position = sprite_Get_Position;
+        fn get_sprite_position_x(sprite: U8) -> I16 = sprite_Get_Position_X;
+        fn get_sprite_position_y(sprite: U8) -> I16 = sprite_Get_Position_Y;
+        fn set_sprite_position(sprite: U8, pos: Position) = sprite_Set_Position;
+        fn set_sprite_flip(sprite: U8, flip_x: Bool, flip_y: Bool) = sprite_Set_Flip;
+        /// Puts the sprite behind the first priority backgrounds, 0 is in front of all of them
+        fn set_sprite_priority(sprite: U8, priority: U8) = sprite_Set_Priority;
+        /// Sets the sprite's size in pixels, each side is 8, 16, 32 or 64. Sprites bigger than a
+        /// tile are made of consecutive tiles, a row at a time.
+        fn set_sprite_size(sprite: U8, width: U8, height: U8) = sprite_Set_Size;
+        /// Sets one of the matrices affine sprites share, each entry is 8.8 fixed point. It
+        /// transforms the screen into the sprite, around the sprite's center.
+        fn write_sprite_matrix(matrix: U8, a: I16, b: I16, c: I16, d: I16) = sprite_WriteMatrix;
+        /// Rotates and scales the sprite with a shared matrix, or draws it normally if matrix is
+        /// -1. Double size sprites are drawn in a box twice as big, so they aren't cut off.
+        fn set_sprite_matrix(sprite: U8, matrix: I32, double_size: Bool) = sprite_Set_Matrix;
+        /// Sets how the sprite's pixels blend with what's under them
+        fn set_sprite_blend(sprite: U8, mode: BlendMode) = sprite_Set_Blend;
+    }
+
+    mod dbg {
+        fn write_character(c: Char) = dbg_WriteCharacter;
+        fn end_line() = dbg_EndLine;
+        fn write_str(s: Str) = dbg_WriteString;
+        fn write_int(i: I64) = dbg_WriteInt;
+        fn write_uint(i: U64) = dbg_WriteUint;
+        fn write_ptr(p: ConstPtr) = dbg_WritePtr;
+    }
+
+    mod gamepad {
+        fn get_state(gamepad: U8) -> GamepadState = gamepad_GetState;
+    }
+
+    /// Save data persists between runs of the cart, it's save_Size() bytes long.
+    mod save {
+        fn size() -> U32 = save_Size;
+        fn read(offset: U32, data: Ptr, len: U32) = save_Read;
+        fn write(offset: U32, data: ConstPtr, len: U32) = save_Write;
+    }
+
+    /// Assets are named blobs of data packaged with the cart.
+    mod asset {
+        /// Returns the size of the asset, or -1 if there isn't one called name.
+        fn size(name: Str) -> I32 = asset_Size;
+        /// Copies up to len bytes of the asset, starting at offset, returning how many were copied.
+        fn read(name: Str, offset: U32, data: Ptr, len: U32) -> U32 = asset_Read;
+    }
+
+    mod system {
+        fn api_version() -> U32 = system_ApiVersion;
+        /// Which FEATURE_ flags the console supports
+        fn features() -> U32 = system_Features;
+        fn screen_width() -> U32 = system_ScreenWidth;
+        fn screen_height() -> U32 = system_ScreenHeight;
+        fn sprite_count() -> U32 = system_SpriteCount;
+        fn background_count() -> U32 = system_BackgroundCount;
+        fn tile_count() -> U32 = system_TileCount;
+        fn palette_size() -> U32 = system_PaletteSize;
+        fn gamepad_count() -> U32 = system_GamepadCount;
+        fn sprite_matrix_count() -> U32 = system_SpriteMatrixCount;
+    }
+}
+
+/// The host functions whose wasm types don't match how [`HOST_ABI`] describes them.
+pub fn host_abi_mismatches() -> wasmtime::Result<Vec<String>> {
+    let engine = Engine::default();
+    let mut linker = Linker::new(&engine);
+    add_host_functions(&mut linker)?;
+    let mut store = Store::new(&engine, HostState::new(&CartConfig::default()));
+
+    let mut mismatches = Vec::new();
+    for module in HOST_ABI {
+        for function in module.functions {
+            let Some(func) = linker
+                .get(&mut store, module.name, function.name)
+                .and_then(|item| item.into_func())
+            else {
+                mismatches.push(format!(
+                    "`{}::{}` isn't in the linker",
+                    module.name, function.name
+                ));
+                continue;
+            };
+            let ty = func.ty(&store);
+            let params: Vec<String> = ty.params().map(|ty| ty.to_string()).collect();
+            let results: Vec<String> = ty.results().map(|ty| ty.to_string()).collect();
+            if params != function.wasm_params() || results != function.wasm_results() {
+                mismatches.push(format!(
+                    "`{}::{}` takes ({}) and returns ({}), but is described as taking ({}) and returning ({})",
+                    module.name,
+                    function.name,
+                    params.join(", "),
+                    results.join(", "),
+                    function.wasm_params().join(", "),
+                    function.wasm_results().join(", "),
+                ));
+            }
         }
     }
+    Ok(mismatches)
+}
+
+mod gamepad {
+    use wasmtime::Caller;
+
+    use crate::gamepad::GamepadState;
+
+    use super::{checked, HostState};
+
+    pub fn get_state(caller: Caller<HostState>, gamepad_idx: u32) -> wasmtime::Result<u32> {
+        let state = caller.data();
+        let gamepad = checked(state.strict, &state.gamepads, gamepad_idx, "gamepad")?;
+        Ok(gamepad
+            .copied()
+            .unwrap_or(GamepadState::Disconnected)
+            .bits() as u32)
+    }
+}
+
+mod dbg {
+    use wasmtime::Caller;
+
+    use super::{checked_c_str, get_memory, HostState};
+
+    pub fn write_character(caller: Caller<HostState>, c: u32) -> wasmtime::Result<()> {
+        let c = match char::from_u32(c) {
+            Some(c) => c,
+            None if caller.data().strict => {
+                return Err(wasmtime::Error::msg(format!(
+                    "{c:#x} isn't a valid character"
+                )));
+            }
+            None => char::REPLACEMENT_CHARACTER,
+        };
+        print!("{c}");
+        Ok(())
+    }
+
+    pub fn write_str(mut caller: Caller<HostState>, s: u32) -> wasmtime::Result<()> {
+        let mem = get_memory(&mut caller)?;
+        let strict = caller.data().strict;
+        match checked_c_str(strict, mem.data(&caller), s)? {
+            Some(cs) if strict => print!("{}", cs.to_str()?),
+            Some(cs) => print!("{}", cs.to_string_lossy()),
+            None => {}
+        }
+        Ok(())
+    }
 
     pub fn write_int(i: i64) {
         print!("{i}");
@@ -170,143 +1104,1119 @@ mod dbg {
 mod tile {
     use wasmtime::Caller;
 
-    use crate::{math::Fixed, tile::TileState};
+    use crate::{
+        math::Fixed,
+        tile::{TileAttributes, TileMap, PALETTE_SIZE, TILE_COUNT},
+    };
+
+    use super::{
+        checked, checked_blend_mode, checked_blend_targets, checked_memory, checked_mut,
+        checked_value, get_memory, HostState,
+    };
+
+    fn background<'a>(
+        caller: &'a Caller<HostState>,
+        bg: u32,
+    ) -> wasmtime::Result<Option<&'a TileMap>> {
+        let state = caller.data();
+        checked(
+            state.strict,
+            &state.tile_state.backgrounds,
+            bg,
+            "background",
+        )
+    }
+
+    fn background_mut<'a>(
+        caller: &'a mut Caller<HostState>,
+        bg: u32,
+    ) -> wasmtime::Result<Option<&'a mut TileMap>> {
+        let state = caller.data_mut();
+        checked_mut(
+            state.strict,
+            &mut state.tile_state.backgrounds,
+            bg,
+            "background",
+        )
+    }
 
-    use super::WasmCart;
+    fn map_index(x: i32, y: i32) -> usize {
+        (x & 0b111111) as usize + (y & 0b111111) as usize * 64
+    }
 
-    pub fn write_palette(palette: u32, color: u32) {
-        TileState::get().palette[(palette & 255) as usize] = color.into();
+    pub fn write_palette(
+        mut caller: Caller<HostState>,
+        palette: u32,
+        color: u32,
+    ) -> wasmtime::Result<()> {
+        let state = caller.data_mut();
+        if let Some(entry) = checked_mut(
+            state.strict,
+            &mut state.tile_state.palette,
+            palette,
+            "palette entry",
+        )? {
+            *entry = color.into();
+        }
+        Ok(())
     }
 
-    pub fn write_tile(mut caller: Caller<()>, tile: u32, data_ptr: u32) {
-        let cart = WasmCart::get();
-        let mem = cart.get_memory(&mut caller);
-        mem.read(
-            caller,
-            data_ptr as usize,
-            &mut TileState::get().tiles[(tile & 255) as usize].0,
-        )
-        .unwrap();
+    pub fn write_tile(
+        mut caller: Caller<HostState>,
+        tile: u32,
+        data_ptr: u32,
+    ) -> wasmtime::Result<()> {
+        let mem = get_memory(&mut caller)?;
+        let (data, state) = mem.data_and_store_mut(&mut caller);
+        let strict = state.strict;
+        let tile = checked_mut(strict, &mut state.tile_state.tiles, tile, "tile")?;
+        if let Some(tile) = tile
+            && let Some(src) = checked_memory(strict, data, data_ptr, tile.0.len())?
+        {
+            tile.0.copy_from_slice(src);
+        }
+        Ok(())
+    }
+
+    pub fn set_background_tile(
+        mut caller: Caller<HostState>,
+        bg: u32,
+        x: i32,
+        y: i32,
+        tile: u32,
+    ) -> wasmtime::Result<()> {
+        let tile = checked_value(caller.data().strict, tile, TILE_COUNT, "tile")?;
+        if let Some(tile) = tile
+            && let Some(bg) = background_mut(&mut caller, bg)?
+        {
+            bg.tiles[map_index(x, y)] = tile;
+        }
+        Ok(())
+    }
+
+    pub fn set_background_palette(
+        mut caller: Caller<HostState>,
+        palette: u32,
+    ) -> wasmtime::Result<()> {
+        let state = caller.data_mut();
+        if let Some(palette) = checked_value(state.strict, palette, PALETTE_SIZE, "palette entry")?
+        {
+            state.tile_state.background_color = palette;
+        }
+        Ok(())
+    }
+
+    pub fn set_background_tile_palette(
+        mut caller: Caller<HostState>,
+        bg: u32,
+        x: i32,
+        y: i32,
+        palette: u32,
+    ) -> wasmtime::Result<()> {
+        let palette = checked_value(caller.data().strict, palette, PALETTE_SIZE, "palette")?;
+        if let Some(palette) = palette
+            && let Some(bg) = background_mut(&mut caller, bg)?
+        {
+            bg.palettes[map_index(x, y)] = palette;
+        }
+        Ok(())
     }
 
-    pub fn set_background_tile(bg: u32, x: u32, y: u32, tile: i32) {
-        TileState::get().backgrounds[bg as usize].tiles
-            [(x & 0b111111) as usize + (y & 0b111111) as usize * 64] = tile as u8;
+    pub fn set_background_tile_flip(
+        mut caller: Caller<HostState>,
+        bg: u32,
+        x: i32,
+        y: i32,
+        flip_x: u32,
+        flip_y: u32,
+    ) -> wasmtime::Result<()> {
+        if let Some(bg) = background_mut(&mut caller, bg)? {
+            let attributes = &mut bg.attributes[map_index(x, y)];
+            attributes.set(TileAttributes::FLIP_X, flip_x != 0);
+            attributes.set(TileAttributes::FLIP_Y, flip_y != 0);
+        }
+        Ok(())
+    }
+
+    pub fn set_background_tile_priority(
+        mut caller: Caller<HostState>,
+        bg: u32,
+        x: i32,
+        y: i32,
+        above_sprites: u32,
+    ) -> wasmtime::Result<()> {
+        if let Some(bg) = background_mut(&mut caller, bg)? {
+            bg.attributes[map_index(x, y)].set(TileAttributes::PRIORITY, above_sprites != 0);
+        }
+        Ok(())
     }
 
-    pub fn set_background_palette(palette: u32) {
-        TileState::get().background_color = palette as u8;
+    pub fn set_background_blend(
+        mut caller: Caller<HostState>,
+        bg: u32,
+        mode: u32,
+    ) -> wasmtime::Result<()> {
+        let mode = checked_blend_mode(caller.data().strict, mode)?;
+        if let Some(mode) = mode
+            && let Some(bg) = background_mut(&mut caller, bg)?
+        {
+            bg.blend = mode;
+        }
+        Ok(())
+    }
+
+    pub fn set_blend_targets(mut caller: Caller<HostState>, targets: u32) -> wasmtime::Result<()> {
+        let state = caller.data_mut();
+        if let Some(targets) = checked_blend_targets(state.strict, targets)? {
+            state.tile_state.blend_targets = targets;
+        }
+        Ok(())
     }
 
-    pub fn set_background_tile_palette(bg: u32, x: i32, y: i32, palette: u32) {
-        TileState::get().backgrounds[bg as usize].palettes
-            [(x & 0b111111) as usize + (y & 0b111111) as usize * 64] = palette as u8;
+    pub fn set_blend_alpha(
+        mut caller: Caller<HostState>,
+        top: u32,
+        below: u32,
+    ) -> wasmtime::Result<()> {
+        let state = caller.data_mut();
+        // Each is out of 16
+        let top = checked_value(state.strict, top, 17, "blend coefficient")?;
+        let below = checked_value(state.strict, below, 17, "blend coefficient")?;
+        if let Some(top) = top
+            && let Some(below) = below
+        {
+            state.tile_state.blend_alpha = (top, below);
+        }
+        Ok(())
     }
 
-    pub fn set_background_visible(bg: u32, visible: u32) {
-        TileState::get().backgrounds[bg as usize].visible = visible != 0;
+    pub fn set_background_visible(
+        mut caller: Caller<HostState>,
+        bg: u32,
+        visible: u32,
+    ) -> wasmtime::Result<()> {
+        if let Some(bg) = background_mut(&mut caller, bg)? {
+            bg.visible = visible != 0;
+        }
+        Ok(())
     }
 
-    pub fn get_background_scroll_pre(bg: u32) -> (u32, u32) {
-        let coords = TileState::get().backgrounds[bg as usize].pre_offset;
-        (i16::from(coords.0) as u32, i16::from(coords.1) as u32)
+    pub fn get_background_scroll_pre(
+        caller: Caller<HostState>,
+        bg: u32,
+    ) -> wasmtime::Result<(u32, u32)> {
+        Ok(background(&caller, bg)?.map_or((0, 0), |bg| {
+            (bg.pre_offset.0 as u32, bg.pre_offset.1 as u32)
+        }))
     }
 
-    pub fn get_background_scroll_pre_x(bg: u32) -> u32 {
-        let coords = TileState::get().backgrounds[bg as usize].pre_offset;
-        i16::from(coords.0) as u32
+    pub fn get_background_scroll_pre_x(
+        caller: Caller<HostState>,
+        bg: u32,
+    ) -> wasmtime::Result<u32> {
+        Ok(background(&caller, bg)?.map_or(0, |bg| bg.pre_offset.0 as u32))
     }
 
-    pub fn get_background_scroll_pre_y(bg: u32) -> u32 {
-        let coords = TileState::get().backgrounds[bg as usize].pre_offset;
-        i16::from(coords.1) as u32
+    pub fn get_background_scroll_pre_y(
+        caller: Caller<HostState>,
+        bg: u32,
+    ) -> wasmtime::Result<u32> {
+        Ok(background(&caller, bg)?.map_or(0, |bg| bg.pre_offset.1 as u32))
     }
 
-    pub fn set_background_scroll_pre(bg: u32, x: i32, y: i32) {
-        TileState::get().backgrounds[bg as usize].pre_offset = (x as i16, y as i16);
+    pub fn set_background_scroll_pre(
+        mut caller: Caller<HostState>,
+        bg: u32,
+        x: i32,
+        y: i32,
+    ) -> wasmtime::Result<()> {
+        if let Some(bg) = background_mut(&mut caller, bg)? {
+            bg.pre_offset = (x as i16, y as i16);
+        }
+        Ok(())
     }
 
-    pub fn set_background_scroll_pre_x(bg: u32, x: i32) {
-        TileState::get().backgrounds[bg as usize].pre_offset.0 = x as i16;
+    pub fn set_background_scroll_pre_x(
+        mut caller: Caller<HostState>,
+        bg: u32,
+        x: i32,
+    ) -> wasmtime::Result<()> {
+        if let Some(bg) = background_mut(&mut caller, bg)? {
+            bg.pre_offset.0 = x as i16;
+        }
+        Ok(())
     }
 
-    pub fn set_background_scroll_pre_y(bg: u32, y: i32) {
-        TileState::get().backgrounds[bg as usize].pre_offset.0 = y as i16;
+    pub fn set_background_scroll_pre_y(
+        mut caller: Caller<HostState>,
+        bg: u32,
+        y: i32,
+    ) -> wasmtime::Result<()> {
+        if let Some(bg) = background_mut(&mut caller, bg)? {
+            bg.pre_offset.1 = y as i16;
+        }
+        Ok(())
     }
 
-    pub fn get_background_scroll_post(bg: u32) -> (u32, u32) {
-        let coords = TileState::get().backgrounds[bg as usize].post_offset;
-        (i16::from(coords.0) as u32, i16::from(coords.1) as u32)
+    pub fn get_background_scroll_post(
+        caller: Caller<HostState>,
+        bg: u32,
+    ) -> wasmtime::Result<(u32, u32)> {
+        Ok(background(&caller, bg)?.map_or((0, 0), |bg| {
+            (bg.post_offset.0 as u32, bg.post_offset.1 as u32)
+        }))
     }
 
-    pub fn get_background_scroll_post_x(bg: u32) -> u32 {
-        let coords = TileState::get().backgrounds[bg as usize].post_offset;
-        i16::from(coords.0) as u32
+    pub fn get_background_scroll_post_x(
+        caller: Caller<HostState>,
+        bg: u32,
+    ) -> wasmtime::Result<u32> {
+        Ok(background(&caller, bg)?.map_or(0, |bg| bg.post_offset.0 as u32))
     }
 
-    pub fn get_background_scroll_post_y(bg: u32) -> u32 {
-        let coords = TileState::get().backgrounds[bg as usize].post_offset;
-        i16::from(coords.1) as u32
+    pub fn get_background_scroll_post_y(
+        caller: Caller<HostState>,
+        bg: u32,
+    ) -> wasmtime::Result<u32> {
+        Ok(background(&caller, bg)?.map_or(0, |bg| bg.post_offset.1 as u32))
     }
 
-    pub fn set_background_scroll_post(bg: u32, x: i32, y: i32) {
-        TileState::get().backgrounds[bg as usize].post_offset = (x as i16, y as i16);
+    pub fn set_background_scroll_post(
+        mut caller: Caller<HostState>,
+        bg: u32,
+        x: i32,
+        y: i32,
+    ) -> wasmtime::Result<()> {
+        if let Some(bg) = background_mut(&mut caller, bg)? {
+            bg.post_offset = (x as i16, y as i16);
+        }
+        Ok(())
     }
 
-    pub fn set_background_scroll_post_x(bg: u32, x: i32) {
-        TileState::get().backgrounds[bg as usize].post_offset.0 = x as i16;
+    pub fn set_background_scroll_post_x(
+        mut caller: Caller<HostState>,
+        bg: u32,
+        x: i32,
+    ) -> wasmtime::Result<()> {
+        if let Some(bg) = background_mut(&mut caller, bg)? {
+            bg.post_offset.0 = x as i16;
+        }
+        Ok(())
     }
 
-    pub fn set_background_scroll_post_y(bg: u32, y: i32) {
-        TileState::get().backgrounds[bg as usize].post_offset.0 = y as i16;
+    pub fn set_background_scroll_post_y(
+        mut caller: Caller<HostState>,
+        bg: u32,
+        y: i32,
+    ) -> wasmtime::Result<()> {
+        if let Some(bg) = background_mut(&mut caller, bg)? {
+            bg.post_offset.1 = y as i16;
+        }
+        Ok(())
     }
 
-    pub fn set_background_transformation_matrix(bg: u32, a: i32, b: i32, c: i32, d: i32) {
-        TileState::get().backgrounds[bg as usize].matrix = (
-            (Fixed::from(a as i16), Fixed::from(b as i16)),
-            (Fixed::from(c as i16), Fixed::from(d as i16)),
-        )
+    pub fn set_background_transformation_matrix(
+        mut caller: Caller<HostState>,
+        bg: u32,
+        a: i32,
+        b: i32,
+        c: i32,
+        d: i32,
+    ) -> wasmtime::Result<()> {
+        if let Some(bg) = background_mut(&mut caller, bg)? {
+            bg.matrix = (
+                (Fixed::from(a as i16), Fixed::from(b as i16)),
+                (Fixed::from(c as i16), Fixed::from(d as i16)),
+            );
+        }
+        Ok(())
     }
 }
 
 mod sprite {
-    use crate::tile::TileState;
+    use wasmtime::Caller;
+
+    use crate::{
+        math::Fixed,
+        tile::{
+            Sprite, BACKGROUND_COUNT, PALETTE_SIZE, SPRITE_MATRIX_COUNT, SPRITE_SIZES, TILE_COUNT,
+        },
+    };
+
+    use super::{checked, checked_blend_mode, checked_mut, checked_value, HostState};
+
+    fn sprite(caller: &Caller<HostState>, sprite: u32) -> wasmtime::Result<Option<Sprite>> {
+        let state = caller.data();
+        Ok(checked(state.strict, &state.tile_state.sprites, sprite, "sprite")?.copied())
+    }
+
+    fn sprite_mut<'a>(
+        caller: &'a mut Caller<HostState>,
+        sprite: u32,
+    ) -> wasmtime::Result<Option<&'a mut Sprite>> {
+        let state = caller.data_mut();
+        checked_mut(
+            state.strict,
+            &mut state.tile_state.sprites,
+            sprite,
+            "sprite",
+        )
+    }
+
+    pub fn set_sprite_visible(
+        mut caller: Caller<HostState>,
+        sprite: u32,
+        visible: u32,
+    ) -> wasmtime::Result<()> {
+        if let Some(sprite) = sprite_mut(&mut caller, sprite)? {
+            sprite.visible = visible != 0;
+        }
+        Ok(())
+    }
+
+    pub fn set_sprite_tile(
+        mut caller: Caller<HostState>,
+        sprite: u32,
+        tile: u32,
+    ) -> wasmtime::Result<()> {
+        let tile = checked_value(caller.data().strict, tile, TILE_COUNT, "tile")?;
+        if let Some(tile) = tile
+            && let Some(sprite) = sprite_mut(&mut caller, sprite)?
+        {
+            sprite.tile = tile;
+        }
+        Ok(())
+    }
+
+    pub fn set_sprite_palette(
+        mut caller: Caller<HostState>,
+        sprite: u32,
+        palette: u32,
+    ) -> wasmtime::Result<()> {
+        let palette = checked_value(caller.data().strict, palette, PALETTE_SIZE, "palette")?;
+        if let Some(palette) = palette
+            && let Some(sprite) = sprite_mut(&mut caller, sprite)?
+        {
+            sprite.palette = palette;
+        }
+        Ok(())
+    }
 
-    pub fn set_sprite_visible(sprite: u32, visible: u32) {
-        TileState::get().sprites[sprite as usize].visible = visible != 0;
+    pub fn get_sprite_position(
+        caller: Caller<HostState>,
+        sprite_idx: u32,
+    ) -> wasmtime::Result<(i32, i32)> {
+        Ok(sprite(&caller, sprite_idx)?.map_or((0, 0), |sprite| {
+            (sprite.position.0 as i32, sprite.position.1 as i32)
+        }))
     }
 
-    pub fn set_sprite_tile(sprite: u32, tile: u32) {
-        TileState::get().sprites[sprite as usize].tile = tile as u8;
+    pub fn get_sprite_position_x(
+        caller: Caller<HostState>,
+        sprite_idx: u32,
+    ) -> wasmtime::Result<i32> {
+        Ok(sprite(&caller, sprite_idx)?.map_or(0, |sprite| sprite.position.0 as i32))
+    }
+
+    pub fn get_sprite_position_y(
+        caller: Caller<HostState>,
+        sprite_idx: u32,
+    ) -> wasmtime::Result<i32> {
+        Ok(sprite(&caller, sprite_idx)?.map_or(0, |sprite| sprite.position.1 as i32))
+    }
+
+    pub fn set_sprite_position(
+        mut caller: Caller<HostState>,
+        sprite: u32,
+        x: i32,
+        y: i32,
+    ) -> wasmtime::Result<()> {
+        if let Some(sprite) = sprite_mut(&mut caller, sprite)? {
+            sprite.position = (x as i16, y as i16);
+        }
+        Ok(())
+    }
+
+    pub fn set_sprite_flip(
+        mut caller: Caller<HostState>,
+        sprite: u32,
+        flip_x: u32,
+        flip_y: u32,
+    ) -> wasmtime::Result<()> {
+        if let Some(sprite) = sprite_mut(&mut caller, sprite)? {
+            sprite.flip_x = flip_x != 0;
+            sprite.flip_y = flip_y != 0;
+        }
+        Ok(())
+    }
+
+    pub fn set_sprite_priority(
+        mut caller: Caller<HostState>,
+        sprite: u32,
+        priority: u32,
+    ) -> wasmtime::Result<()> {
+        let priority = checked_value(
+            caller.data().strict,
+            priority,
+            BACKGROUND_COUNT + 1,
+            "priority",
+        )?;
+        if let Some(priority) = priority
+            && let Some(sprite) = sprite_mut(&mut caller, sprite)?
+        {
+            sprite.priority = priority;
+        }
+        Ok(())
     }
 
-    pub fn set_sprite_palette(sprite: u32, palette: u32) {
-        TileState::get().sprites[sprite as usize].palette = palette as u8;
+    pub fn set_sprite_size(
+        mut caller: Caller<HostState>,
+        sprite: u32,
+        width: u32,
+        height: u32,
+    ) -> wasmtime::Result<()> {
+        let valid = |size: u32| SPRITE_SIZES.iter().any(|&valid| valid as u32 == size);
+        if !valid(width) || !valid(height) {
+            if caller.data().strict {
+                return Err(wasmtime::Error::msg(format!(
+                    "sprite size {width}x{height} is invalid, expected each side to be one of {SPRITE_SIZES:?}"
+                )));
+            }
+            return Ok(());
+        }
+        if let Some(sprite) = sprite_mut(&mut caller, sprite)? {
+            sprite.size = (width as u8, height as u8);
+        }
+        Ok(())
     }
 
-    pub fn get_sprite_position(sprite: u32) -> (i32, i32) {
-        let pos = TileState::get().sprites[sprite as usize].position;
+    pub fn write_sprite_matrix(
+        mut caller: Caller<HostState>,
+        matrix: u32,
+        a: i32,
+        b: i32,
+        c: i32,
+        d: i32,
+    ) -> wasmtime::Result<()> {
+        let state = caller.data_mut();
+        if let Some(matrix) = checked_mut(
+            state.strict,
+            &mut state.tile_state.sprite_matrices,
+            matrix,
+            "sprite matrix",
+        )? {
+            *matrix = (
+                (Fixed::from(a as i16), Fixed::from(b as i16)),
+                (Fixed::from(c as i16), Fixed::from(d as i16)),
+            );
+        }
+        Ok(())
+    }
 
-        (pos.0 as i32, pos.1 as i32)
+    /// `matrix` is -1 to draw the sprite without one
+    pub fn set_sprite_matrix(
+        mut caller: Caller<HostState>,
+        sprite: u32,
+        matrix: i32,
+        double_size: u32,
+    ) -> wasmtime::Result<()> {
+        let strict = caller.data().strict;
+        let matrix = match matrix {
+            -1 => None,
+            matrix => {
+                match checked_value(strict, matrix as u32, SPRITE_MATRIX_COUNT, "sprite matrix")? {
+                    Some(matrix) => Some(matrix),
+                    None => return Ok(()),
+                }
+            }
+        };
+        if let Some(sprite) = sprite_mut(&mut caller, sprite)? {
+            sprite.matrix = matrix;
+            sprite.double_size = double_size != 0;
+        }
+        Ok(())
     }
 
-    pub fn get_sprite_position_x(sprite: u32) -> i32 {
-        TileState::get().sprites[sprite as usize].position.0 as i32
+    pub fn set_sprite_blend(
+        mut caller: Caller<HostState>,
+        sprite: u32,
+        mode: u32,
+    ) -> wasmtime::Result<()> {
+        let mode = checked_blend_mode(caller.data().strict, mode)?;
+        if let Some(mode) = mode
+            && let Some(sprite) = sprite_mut(&mut caller, sprite)?
+        {
+            sprite.blend = mode;
+        }
+        Ok(())
     }
+}
+
+mod save {
+    use wasmtime::Caller;
+
+    use crate::save_data::SAVE_SIZE;
 
-    pub fn get_sprite_position_y(sprite: u32) -> i32 {
-        TileState::get().sprites[sprite as usize].position.1 as i32
+    use super::{checked_memory, checked_memory_mut, get_memory, HostState};
+
+    /// Checks that `len` bytes at `offset` are within the save data.
+    fn checked_range(strict: bool, offset: u32, len: u32) -> wasmtime::Result<Option<usize>> {
+        if offset as usize + len as usize <= SAVE_SIZE {
+            Ok(Some(offset as usize))
+        } else if strict {
+            Err(wasmtime::Error::msg(format!(
+                "{len} bytes at {offset:#x} are out of bounds, save data is {SAVE_SIZE} bytes"
+            )))
+        } else {
+            Ok(None)
+        }
     }
 
-    pub fn set_sprite_position(sprite: u32, x: i32, y: i32) {
-        TileState::get().sprites[sprite as usize].position = (x as i16, y as i16);
+    pub fn size() -> u32 {
+        SAVE_SIZE as u32
     }
 
-    pub fn set_sprite_flip(sprite: u32, flip_x: u32, flip_y: u32) {
-        let sprite = &mut TileState::get().sprites[sprite as usize];
+    pub fn read(
+        mut caller: Caller<HostState>,
+        offset: u32,
+        data_ptr: u32,
+        len: u32,
+    ) -> wasmtime::Result<()> {
+        let mem = get_memory(&mut caller)?;
+        let (data, state) = mem.data_and_store_mut(&mut caller);
+        let strict = state.strict;
+        if let Some(offset) = checked_range(strict, offset, len)?
+            && let Some(dest) = checked_memory_mut(strict, data, data_ptr, len as usize)?
+        {
+            dest.copy_from_slice(&state.save_data.bytes[offset..][..len as usize]);
+        }
+        Ok(())
+    }
 
-        sprite.flip_x = flip_x != 0;
-        sprite.flip_y = flip_y != 0;
+    pub fn write(
+        mut caller: Caller<HostState>,
+        offset: u32,
+        data_ptr: u32,
+        len: u32,
+    ) -> wasmtime::Result<()> {
+        let mem = get_memory(&mut caller)?;
+        let (data, state) = mem.data_and_store_mut(&mut caller);
+        let strict = state.strict;
+        if let Some(offset) = checked_range(strict, offset, len)?
+            && let Some(src) = checked_memory(strict, data, data_ptr, len as usize)?
+        {
+            state.save_data.write(offset, src);
+        }
+        Ok(())
     }
 }
 
+mod asset {
+    use wasmtime::Caller;
+
+    use super::{checked_c_str, checked_memory_mut, get_memory, HostState};
+
+    /// The size of the asset called `name`, or -1 if there isn't one.
+    pub fn size(mut caller: Caller<HostState>, name: u32) -> wasmtime::Result<i32> {
+        let mem = get_memory(&mut caller)?;
+        let (data, state) = mem.data_and_store_mut(&mut caller);
+        let Some(name) = checked_c_str(state.strict, data, name)? else {
+            return Ok(-1);
+        };
+        Ok(name
+            .to_str()
+            .ok()
+            .and_then(|name| state.assets.get(name))
+            .map_or(-1, |asset| asset.len() as i32))
+    }
+
+    /// Copies up to `len` bytes of the asset called `name`, starting at `offset`, to `data_ptr`.
+    /// Returns how many were copied, which is 0 if there's no asset called `name`.
+    pub fn read(
+        mut caller: Caller<HostState>,
+        name: u32,
+        offset: u32,
+        data_ptr: u32,
+        len: u32,
+    ) -> wasmtime::Result<u32> {
+        let mem = get_memory(&mut caller)?;
+        let (data, state) = mem.data_and_store_mut(&mut caller);
+        let strict = state.strict;
+        let Some(name) = checked_c_str(strict, data, name)? else {
+            return Ok(0);
+        };
+        let Some(asset) = name.to_str().ok().and_then(|name| state.assets.get(name)) else {
+            return Ok(0);
+        };
+
+        let src = asset.get(offset as usize..).unwrap_or_default();
+        let len = src.len().min(len as usize);
+        if let Some(dest) = checked_memory_mut(strict, data, data_ptr, len)? {
+            dest.copy_from_slice(&src[..len]);
+            Ok(len as u32)
+        } else {
+            Ok(0)
+        }
+    }
+}
+
+mod system {
+    use wasmtime::Caller;
+
+    use crate::{
+        frame::{HEIGHT, WIDTH},
+        system::API_VERSION,
+        tile::{BACKGROUND_COUNT, PALETTE_SIZE, SPRITE_COUNT, SPRITE_MATRIX_COUNT, TILE_COUNT},
+    };
+
+    use super::HostState;
+
+    pub fn api_version() -> u32 {
+        API_VERSION
+    }
+
+    pub fn features(caller: Caller<HostState>) -> u32 {
+        caller.data().features.bits()
+    }
+
+    pub fn screen_width() -> u32 {
+        WIDTH as u32
+    }
+
+    pub fn screen_height() -> u32 {
+        HEIGHT as u32
+    }
+
+    pub fn sprite_count() -> u32 {
+        SPRITE_COUNT as u32
+    }
+
+    pub fn background_count() -> u32 {
+        BACKGROUND_COUNT as u32
+    }
+
+    pub fn tile_count() -> u32 {
+        TILE_COUNT as u32
+    }
+
+    pub fn palette_size() -> u32 {
+        PALETTE_SIZE as u32
+    }
+
+    pub fn gamepad_count() -> u32 {
+        4
+    }
+
+    pub fn sprite_matrix_count() -> u32 {
+        SPRITE_MATRIX_COUNT as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, console::Console};
+
+    fn config(strict: bool) -> CartConfig {
+        CartConfig {
+            strict,
+            budget: None,
+            cache_dir: None,
+            ..CartConfig::default()
+        }
+    }
+
+    #[test]
+    fn from_wat_calls_host_functions() {
+        let mut cart = WasmCart::from_wat(
+            r#"(module
+                (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+                (func (export "update")
+                    (call $write_palette (i32.const 3) (i32.const 0x123456))))"#,
+            config(true),
+        )
+        .unwrap();
+        cart.update().unwrap();
+        assert_eq!(cart.state().tile_state.palette[3], Color::from(0x123456));
+    }
+
+    const OUT_OF_RANGE_PALETTE: &str = r#"(module
+        (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+        (func (export "update")
+            (call $write_palette (i32.const 256) (i32.const 0xffffff))))"#;
+
+    #[test]
+    fn strict_traps_out_of_range() {
+        let mut cart = WasmCart::from_wat(OUT_OF_RANGE_PALETTE, config(true)).unwrap();
+        match cart.update() {
+            Err(CartError::Trap { message, .. }) => {
+                assert_eq!(
+                    message,
+                    "palette entry 256 is out of range, expected less than 256"
+                );
+            }
+            result => panic!("expected a trap, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn not_strict_ignores_out_of_range() {
+        let mut cart = WasmCart::from_wat(OUT_OF_RANGE_PALETTE, config(false)).unwrap();
+        cart.update().unwrap();
+        assert!(cart
+            .state()
+            .tile_state
+            .palette
+            .iter()
+            .all(|&color| color == Color::default()));
+    }
+
+    #[test]
+    fn scroll_setters_set_their_axis() {
+        let mut cart = WasmCart::from_wat(
+            r#"(module
+                (import "tile" "set_background_scroll_pre_x" (func $pre_x (param i32 i32)))
+                (import "tile" "set_background_scroll_pre_y" (func $pre_y (param i32 i32)))
+                (import "tile" "set_background_scroll_post_x" (func $post_x (param i32 i32)))
+                (import "tile" "set_background_scroll_post_y" (func $post_y (param i32 i32)))
+                (func (export "update")
+                    (call $pre_x (i32.const 1) (i32.const 3))
+                    (call $pre_y (i32.const 1) (i32.const 5))
+                    (call $post_x (i32.const 1) (i32.const -2))
+                    (call $post_y (i32.const 1) (i32.const -7))))"#,
+            config(true),
+        )
+        .unwrap();
+        cart.update().unwrap();
+        let bg = &cart.state().tile_state.backgrounds[1];
+        assert_eq!(bg.pre_offset, (3, 5));
+        assert_eq!(bg.post_offset, (-2, -7));
+    }
+
+    /// Counts to `iterations` in `update`, for using a known amount of fuel
+    fn counting_cart(iterations: i32, overrun: OverrunPolicy) -> WasmCart {
+        let wat = format!(
+            r#"(module
+                (func (export "update") (local $i i32)
+                    (loop $count
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br_if $count (i32.lt_u (local.get $i) (i32.const {iterations}))))))"#
+        );
+        let config = CartConfig {
+            budget: Some(CpuBudget {
+                frame: 1000,
+                scanline: 100,
+                hard_limit: 10,
+                overrun,
+            }),
+            ..config(true)
+        };
+        WasmCart::from_wat(&wat, config).unwrap()
+    }
+
+    #[test]
+    fn under_budget() {
+        let mut cart = counting_cart(10, OverrunPolicy::Trap);
+        cart.update().unwrap();
+        assert!(cart.metrics().update > 0);
+        assert!(!cart.metrics().overrun());
+    }
+
+    #[test]
+    fn overrun_is_measured() {
+        let mut cart = counting_cart(300, OverrunPolicy::Slowdown);
+        cart.update().unwrap();
+        assert!(cart.metrics().update > 1000);
+        assert!(cart.metrics().update_overrun);
+    }
+
+    #[test]
+    fn overrun_traps_with_trap_policy() {
+        let mut cart = counting_cart(300, OverrunPolicy::Trap);
+        match cart.update() {
+            Err(CartError::Overrun {
+                export: "update",
+                used,
+                budget: 1000,
+            }) => assert!(used > 1000),
+            result => panic!("expected an overrun, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn hard_limit_runs_out_of_fuel() {
+        let mut cart = counting_cart(i32::MAX, OverrunPolicy::Slowdown);
+        match cart.update() {
+            Err(CartError::Trap { trap, .. }) => assert_eq!(trap, Some(Trap::OutOfFuel)),
+            result => panic!("expected running out of fuel, got {result:?}"),
+        }
+    }
+
+    /// Each `update` counts up, keeping the count in a global, in memory and in the palette. On
+    /// the second frame it grows its memory.
+    const COUNTER: &str = r#"(module
+        (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+        (memory (export "memory") 1)
+        (global $count (export "count") (mut i32) (i32.const 0))
+        (func (export "update")
+            (global.set $count (i32.add (global.get $count) (i32.const 1)))
+            (i32.store (i32.const 0) (global.get $count))
+            (call $write_palette (i32.const 0) (global.get $count))
+            (if (i32.eq (global.get $count) (i32.const 2))
+                (then (drop (memory.grow (i32.const 1)))))))"#;
+
+    /// The count in the global, in memory and in the palette, and how big memory is
+    fn count(cart: &mut WasmCart) -> (i32, i32, u32, usize) {
+        let global = cart.instance.get_global(&mut cart.store, "count").unwrap();
+        let global = global.get(&mut cart.store).unwrap_i32();
+        let memory = cart.memory().unwrap().data(&cart.store);
+        let in_memory = i32::from_le_bytes(memory[..4].try_into().unwrap());
+        let palette = u32::from(cart.state().tile_state.palette[0]);
+        (global, in_memory, palette, cart.memory_size())
+    }
+
+    #[test]
+    fn save_state_round_trips() {
+        let mut cart = WasmCart::from_wat(COUNTER, config(true)).unwrap();
+        cart.update().unwrap();
+        let state = cart.save_state().unwrap();
+        let saved = count(&mut cart);
+        assert_eq!(saved, (1, 1, 1, PAGE_SIZE));
+
+        cart.update().unwrap();
+        cart.update().unwrap();
+        assert_eq!(count(&mut cart), (3, 3, 3, 2 * PAGE_SIZE));
+
+        // Through its file format too, loading it shrinks memory back down
+        let state = SaveState::from_bytes(&state.to_bytes()).unwrap();
+        cart.load_state(&state).unwrap();
+        assert_eq!(count(&mut cart), saved);
+    }
+
+    #[test]
+    fn save_state_is_for_one_cart() {
+        let mut cart = WasmCart::from_wat(COUNTER, config(true)).unwrap();
+        let state = cart.save_state().unwrap();
+        let mut other = WasmCart::from_wat("(module)", config(true)).unwrap();
+        assert!(other.load_state(&state).is_err());
+    }
+
+    #[test]
+    fn strict_traps_unknown_blend_targets() {
+        let wat = |targets: u32| {
+            format!(
+                r#"(module
+                    (import "tile" "set_blend_targets" (func $set_blend_targets (param i32)))
+                    (func (export "update") (call $set_blend_targets (i32.const {targets}))))"#
+            )
+        };
+        let all = BlendTargets::all().bits() as u32;
+
+        let mut cart = WasmCart::from_wat(&wat(all), config(true)).unwrap();
+        cart.update().unwrap();
+        assert_eq!(cart.state().tile_state.blend_targets, BlendTargets::all());
+
+        for targets in [all + 1, 0x10000] {
+            let mut cart = WasmCart::from_wat(&wat(targets), config(true)).unwrap();
+            assert!(matches!(cart.update(), Err(CartError::Trap { .. })));
+
+            let mut cart = WasmCart::from_wat(&wat(targets), config(false)).unwrap();
+            let before = cart.state().tile_state.blend_targets;
+            cart.update().unwrap();
+            assert_eq!(cart.state().tile_state.blend_targets, before);
+        }
+    }
+
+    /// Counts frames into the first palette entry, running `body` on the first one
+    fn failing_once(body: &str) -> Console {
+        let wat = format!(
+            r#"(module
+                (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+                (global $frame (mut i32) (i32.const 0))
+                (func (export "update")
+                    (global.set $frame (i32.add (global.get $frame) (i32.const 1)))
+                    (call $write_palette (i32.const 0) (global.get $frame))
+                    (if (i32.eq (global.get $frame) (i32.const 1)) (then {body}))))"#
+        );
+        let config = CartConfig {
+            budget: Some(CpuBudget::default()),
+            ..config(true)
+        };
+        Console::new(WasmCart::from_wat(&wat, config).unwrap())
+    }
+
+    /// The console keeps running frames after the first one failed
+    fn keeps_running(console: &mut Console) {
+        console.run_frame().unwrap();
+        console.run_frame().unwrap();
+        assert_eq!(u32::from(console.cart.state().tile_state.palette[0]), 3);
+    }
+
+    #[test]
+    fn unreachable_traps() {
+        let mut console = failing_once("unreachable");
+        match console.run_frame() {
+            Err(CartError::Trap {
+                export: "update",
+                trap,
+                backtrace,
+                ..
+            }) => {
+                assert_eq!(trap, Some(Trap::UnreachableCodeReached));
+                assert!(backtrace.is_some());
+            }
+            result => panic!("expected a trap, got {result:?}"),
+        }
+        keeps_running(&mut console);
+    }
+
+    #[test]
+    fn infinite_loop_runs_out_of_fuel() {
+        let mut console = failing_once("(loop $forever (br $forever))");
+        match console.run_frame() {
+            Err(CartError::Trap {
+                export: "update",
+                trap,
+                ..
+            }) => assert_eq!(trap, Some(Trap::OutOfFuel)),
+            result => panic!("expected running out of fuel, got {result:?}"),
+        }
+        keeps_running(&mut console);
+    }
+
+    #[test]
+    fn host_panics_keep_their_message() {
+        let payload = panic::catch_unwind(|| panic!("boom")).unwrap_err();
+        let err = CartError::panic("update", payload);
+        assert_eq!(err.to_string(), "`update` panicked: boom");
+
+        let payload = panic::catch_unwind(|| panic!("{} boom", 2)).unwrap_err();
+        let err = CartError::panic("draw", payload);
+        assert_eq!(err.to_string(), "`draw` panicked: 2 boom");
+    }
+
+    #[test]
+    fn only_update_is_needed() {
+        let cart = WasmCart::from_wat(r#"(module (func (export "update")))"#, config(true));
+        let mut console = Console::new(cart.unwrap());
+        console.init().unwrap();
+        console.run_frame().unwrap();
+        console.cart.on_pause().unwrap();
+        console.cart.on_resume().unwrap();
+        console.cart.on_reset().unwrap();
+        console.cart.on_exit().unwrap();
+    }
+
+    #[test]
+    fn hooks_fire() {
+        let mut cart = WasmCart::from_wat(
+            r#"(module
+                (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+                (func (export "update"))
+                (func (export "on_pause") (call $write_palette (i32.const 0) (i32.const 1)))
+                (func (export "on_resume") (call $write_palette (i32.const 1) (i32.const 2)))
+                (func (export "on_reset") (call $write_palette (i32.const 2) (i32.const 3)))
+                (func (export "on_exit") (call $write_palette (i32.const 3) (i32.const 4))))"#,
+            config(true),
+        )
+        .unwrap();
+        let fired = |cart: &WasmCart| {
+            let palette = &cart.state().tile_state.palette[..4];
+            palette
+                .iter()
+                .map(|&color| u32::from(color))
+                .collect::<Vec<_>>()
+        };
+
+        cart.on_pause().unwrap();
+        assert_eq!(fired(&cart), [1, 0, 0, 0]);
+        cart.on_resume().unwrap();
+        assert_eq!(fired(&cart), [1, 2, 0, 0]);
+        cart.on_reset().unwrap();
+        assert_eq!(fired(&cart), [1, 2, 3, 0]);
+        cart.on_exit().unwrap();
+        assert_eq!(fired(&cart), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn hooks_have_to_have_the_right_type() {
+        let result = WasmCart::from_wat(
+            r#"(module (func (export "on_pause") (param i32)))"#,
+            config(true),
+        );
+        let err = result
+            .err()
+            .expect("a hook with the wrong type shouldn't load");
+        assert!(format!("{err:#}").contains("`on_pause` has the wrong type"));
+    }
+
+    /// A cart asking for `api_version` in its metadata
+    fn versioned_cart(api_version: u32) -> wasmtime::Result<WasmCart> {
+        let wasm = wat::parse_str(format!(
+            r#"(module
+                (@custom "cart_metadata" "api_version={api_version}")
+                (func (export "update")))"#
+        ))?;
+        WasmCart::from_bytes(&wasm, config(true))
+    }
+
+    #[test]
+    fn newer_api_versions_are_rejected() {
+        versioned_cart(API_VERSION).unwrap();
+        let err = versioned_cart(API_VERSION + 1).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "cart needs API version {}, but this console only supports up to {API_VERSION}",
+                API_VERSION + 1
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_imports_are_rejected() {
+        let result = WasmCart::from_wat(
+            r#"(module
+                (import "tile" "write_palette" (func (param i32 i32)))
+                (import "tile" "write_everything" (func))
+                (import "network" "connect" (func (param i32))))"#,
+            config(true),
+        );
+        let err = result.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "cart imports `tile::write_everything`, `network::connect`, which this console \
+                 doesn't have (it supports API version {API_VERSION})"
+            )
+        );
+    }
+
+    #[test]
+    fn memory_grows_up_to_the_limit() {
+        // Each update grows memory by a page, putting what memory.grow returned in the palette
+        let wat = r#"(module
+            (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+            (memory (export "memory") 1)
+            (global $frame (mut i32) (i32.const 0))
+            (func (export "update")
+                (call $write_palette (global.get $frame) (memory.grow (i32.const 1)))
+                (global.set $frame (i32.add (global.get $frame) (i32.const 1)))))"#;
+        let config = CartConfig {
+            memory_limit: 3 * PAGE_SIZE,
+            ..config(true)
+        };
+        let mut cart = WasmCart::from_wat(wat, config).unwrap();
+        for _ in 0..4 {
+            cart.update().unwrap();
+        }
+
+        let grown: Vec<u32> = cart.state().tile_state.palette[..4]
+            .iter()
+            .map(|&color| u32::from(color))
+            .collect();
+        // The palette drops the top byte of -1
+        assert_eq!(grown, [1, 2, 0xffffff, 0xffffff]);
+        assert_eq!(cart.memory_size(), 3 * PAGE_SIZE);
+    }
+}
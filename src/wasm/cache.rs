@@ -0,0 +1,202 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    env, fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use sha2::{Digest, Sha256};
+use wasmtime::{Engine, Module};
+
+/// How many compiled carts to keep, the least recently used are removed past this
+const MAX_ENTRIES: usize = 64;
+
+/// `CONSOLE_CART_CACHE`, or `console/carts` in the user's cache directory.
+pub fn default_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("CONSOLE_CART_CACHE") {
+        return Some(PathBuf::from(dir));
+    }
+    let cache = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(cache.join("console").join("carts"))
+}
+
+/// Compiles a module from wasm or wasm text, loading it from `dir` instead if it's been compiled
+/// before with the same engine configuration. Problems with the cache only mean compiling again.
+pub fn compile(engine: &Engine, source: &[u8], dir: Option<&Path>) -> wasmtime::Result<Module> {
+    let Some(dir) = dir else {
+        return Module::new(engine, source);
+    };
+
+    let mut compatibility = DefaultHasher::new();
+    engine
+        .precompile_compatibility_hash()
+        .hash(&mut compatibility);
+    let path = dir.join(format!(
+        "{}-{:016x}.cwasm",
+        hex(&Sha256::digest(source)),
+        compatibility.finish()
+    ));
+
+    if let Some(module) = read(engine, &path) {
+        return Ok(module);
+    }
+
+    let module = Module::new(engine, source)?;
+    if let Ok(serialized) = module.serialize() {
+        let _ = write(dir, &path, &serialized);
+    }
+    Ok(module)
+}
+
+/// Entries start with the SHA-256 hash of the serialized module, so a truncated or corrupted file
+/// is never handed to `Module::deserialize`.
+fn read(engine: &Engine, path: &Path) -> Option<Module> {
+    let bytes = fs::read(path).ok()?;
+    let (hash, serialized) = bytes.split_at_checked(32)?;
+    if Sha256::digest(serialized).as_slice() != hash {
+        return None;
+    }
+    // Marks it as recently used
+    let _ = fs::File::options()
+        .append(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+
+    // SAFETY: the hash only catches accidental corruption, anyone who can write to the cache can
+    // make it match. This relies on the cache directory being trusted and only writable by the
+    // user, which is why `write` creates it private. `deserialize` checks the engine's compatible
+    unsafe { Module::deserialize(engine, serialized) }.ok()
+}
+
+fn write(dir: &Path, path: &Path, serialized: &[u8]) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    // Only the user can put modules in it, see `read`
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)?;
+
+    let mut bytes = Sha256::digest(serialized).to_vec();
+    bytes.extend_from_slice(serialized);
+    // Another console could be reading it, so it's replaced all at once
+    let temp = path.with_extension("cwasm.tmp");
+    fs::write(&temp, bytes)?;
+    fs::rename(&temp, path)?;
+
+    prune(dir)
+}
+
+fn prune(dir: &Path) -> io::Result<()> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.path().extension().is_some_and(|ext| ext == "cwasm") {
+            entries.push((entry.metadata()?.modified()?, entry.path()));
+        }
+    }
+    if entries.len() <= MAX_ENTRIES {
+        return Ok(());
+    }
+
+    entries.sort();
+    for (_, path) in &entries[..entries.len() - MAX_ENTRIES] {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("console-cache-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn entries(dir: &Path) -> Vec<PathBuf> {
+        let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    fn exports(module: &Module) -> Vec<&str> {
+        module.exports().map(|export| export.name()).collect()
+    }
+
+    const CART: &str = r#"(module (func (export "update")))"#;
+
+    #[test]
+    fn hit() {
+        let engine = Engine::default();
+        let dir = dir("hit");
+        compile(&engine, CART.as_bytes(), Some(&dir)).unwrap();
+        let [entry] = &entries(&dir)[..] else {
+            panic!("one entry");
+        };
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                fs::metadata(&dir).unwrap().permissions().mode() & 0o777,
+                0o700
+            );
+        }
+
+        assert!(read(&engine, entry).is_some());
+        let module = compile(&engine, CART.as_bytes(), Some(&dir)).unwrap();
+        assert_eq!(exports(&module), ["update"]);
+        assert_eq!(entries(&dir), [entry.clone()]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn miss_after_the_wasm_changes() {
+        let engine = Engine::default();
+        let dir = dir("miss_after_the_wasm_changes");
+        compile(&engine, CART.as_bytes(), Some(&dir)).unwrap();
+
+        let changed = r#"(module (func (export "update")) (func (export "init")))"#;
+        let module = compile(&engine, changed.as_bytes(), Some(&dir)).unwrap();
+        assert_eq!(exports(&module), ["update", "init"]);
+        assert_eq!(entries(&dir).len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn broken_entries_are_compiled_again() {
+        let engine = Engine::default();
+        let dir = dir("broken_entries_are_compiled_again");
+        compile(&engine, CART.as_bytes(), Some(&dir)).unwrap();
+        let entry = entries(&dir).remove(0);
+        let bytes = fs::read(&entry).unwrap();
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        for broken in [&bytes[..bytes.len() / 2], &corrupted, &[]] {
+            fs::write(&entry, broken).unwrap();
+            assert!(read(&engine, &entry).is_none());
+
+            let module = compile(&engine, CART.as_bytes(), Some(&dir)).unwrap();
+            assert_eq!(exports(&module), ["update"]);
+            // And the entry's replaced
+            assert_eq!(fs::read(&entry).unwrap(), bytes);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt::Display,
+    hash::{BuildHasher, Hasher},
+    io::{self, Write},
+    time::{Instant, SystemTime},
+};
+
+use wasmtime::{Caller, Linker};
+
+use super::{checked_memory, checked_memory_mut, get_memory, HostState};
+
+const MODULE: &str = "wasi_snapshot_preview1";
+
+const SUCCESS: u32 = 0;
+const BADF: u32 = 8;
+const FAULT: u32 = 21;
+const INVAL: u32 = 28;
+const SPIPE: u32 = 70;
+
+const CLOCK_REALTIME: u32 = 0;
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+
+/// Returned from `proc_exit`, which stops the cart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CartExit(pub u32);
+
+impl Display for CartExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cart exited with code {}", self.0)
+    }
+}
+
+impl std::error::Error for CartExit {}
+
+pub struct WasiState {
+    start: Instant,
+    /// xorshift64* state for `random_get`
+    rng: u64,
+}
+
+impl WasiState {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            // xorshift can't start from 0
+            rng: RandomState::new().build_hasher().finish() | 1,
+        }
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+pub fn add_to_linker(linker: &mut Linker<HostState>) -> wasmtime::Result<()> {
+    linker.func_wrap(MODULE, "fd_write", fd_write)?;
+    linker.func_wrap(MODULE, "fd_read", fd_read)?;
+    linker.func_wrap(MODULE, "fd_close", fd_close)?;
+    linker.func_wrap(MODULE, "fd_seek", fd_seek)?;
+    linker.func_wrap(MODULE, "fd_fdstat_get", fd_fdstat_get)?;
+    linker.func_wrap(MODULE, "fd_prestat_get", fd_prestat_get)?;
+    linker.func_wrap(MODULE, "fd_prestat_dir_name", fd_prestat_dir_name)?;
+    linker.func_wrap(MODULE, "args_sizes_get", sizes_get)?;
+    linker.func_wrap(MODULE, "args_get", strings_get)?;
+    linker.func_wrap(MODULE, "environ_sizes_get", sizes_get)?;
+    linker.func_wrap(MODULE, "environ_get", strings_get)?;
+    linker.func_wrap(MODULE, "clock_time_get", clock_time_get)?;
+    linker.func_wrap(MODULE, "random_get", random_get)?;
+    linker.func_wrap(MODULE, "sched_yield", || SUCCESS)?;
+    linker.func_wrap(MODULE, "proc_exit", proc_exit)?;
+    Ok(())
+}
+
+/// Writes a little endian value to memory, returning `FAULT` if it's out of bounds.
+fn write_value<const N: usize>(
+    caller: &mut Caller<HostState>,
+    ptr: u32,
+    bytes: [u8; N],
+) -> wasmtime::Result<u32> {
+    let mem = get_memory(caller)?;
+    let (data, state) = mem.data_and_store_mut(caller);
+    match checked_memory_mut(state.strict, data, ptr, N)? {
+        Some(dest) => {
+            dest.copy_from_slice(&bytes);
+            Ok(SUCCESS)
+        }
+        None => Ok(FAULT),
+    }
+}
+
+/// Writes stdout and stderr to the debug console, like the `dbg` module.
+fn fd_write(
+    mut caller: Caller<HostState>,
+    fd: u32,
+    iovs: u32,
+    iovs_len: u32,
+    nwritten: u32,
+) -> wasmtime::Result<u32> {
+    let mut out: Box<dyn Write> = match fd {
+        1 => Box::new(io::stdout()),
+        2 => Box::new(io::stderr()),
+        _ => return Ok(BADF),
+    };
+
+    let mem = get_memory(&mut caller)?;
+    let strict = caller.data().strict;
+    let data = mem.data(&caller);
+
+    let mut written = 0u32;
+    for i in 0..iovs_len {
+        let Some(iov) = checked_memory(strict, data, iovs.wrapping_add(i.wrapping_mul(8)), 8)?
+        else {
+            return Ok(FAULT);
+        };
+        let ptr = u32::from_le_bytes(iov[..4].try_into()?);
+        let len = u32::from_le_bytes(iov[4..].try_into()?);
+        let Some(bytes) = checked_memory(strict, data, ptr, len as usize)? else {
+            return Ok(FAULT);
+        };
+        // The console isn't somewhere the cart can do anything about errors
+        let _ = out.write_all(bytes);
+        written = written.wrapping_add(len);
+    }
+    let _ = out.flush();
+
+    write_value(&mut caller, nwritten, written.to_le_bytes())
+}
+
+/// Stdin is always at its end.
+fn fd_read(
+    mut caller: Caller<HostState>,
+    fd: u32,
+    _iovs: u32,
+    _iovs_len: u32,
+    nread: u32,
+) -> wasmtime::Result<u32> {
+    if fd != 0 {
+        return Ok(BADF);
+    }
+    write_value(&mut caller, nread, 0u32.to_le_bytes())
+}
+
+fn fd_close(_fd: u32) -> u32 {
+    BADF
+}
+
+fn fd_seek(fd: u32, _offset: i64, _whence: u32, _new_offset: u32) -> u32 {
+    if fd <= 2 {
+        SPIPE
+    } else {
+        BADF
+    }
+}
+
+/// Stdin, stdout and stderr are character devices, there aren't any other files.
+fn fd_fdstat_get(mut caller: Caller<HostState>, fd: u32, stat: u32) -> wasmtime::Result<u32> {
+    if fd > 2 {
+        return Ok(BADF);
+    }
+    // The filetype, then flags, then rights, which are all zero
+    let mut bytes = [0; 24];
+    bytes[0] = FILETYPE_CHARACTER_DEVICE;
+    write_value(&mut caller, stat, bytes)
+}
+
+/// There aren't any preopened directories.
+fn fd_prestat_get(_fd: u32, _prestat: u32) -> u32 {
+    BADF
+}
+
+fn fd_prestat_dir_name(_fd: u32, _path: u32, _path_len: u32) -> u32 {
+    BADF
+}
+
+/// There aren't any arguments or environment variables.
+fn sizes_get(mut caller: Caller<HostState>, count: u32, buf_size: u32) -> wasmtime::Result<u32> {
+    match write_value(&mut caller, count, 0u32.to_le_bytes())? {
+        SUCCESS => write_value(&mut caller, buf_size, 0u32.to_le_bytes()),
+        errno => Ok(errno),
+    }
+}
+
+fn strings_get(_strings: u32, _buf: u32) -> u32 {
+    SUCCESS
+}
+
+/// The realtime clock is the host's, every other clock counts from when the cart was loaded.
+fn clock_time_get(
+    mut caller: Caller<HostState>,
+    clock: u32,
+    _precision: u64,
+    time: u32,
+) -> wasmtime::Result<u32> {
+    let nanos = match clock {
+        CLOCK_REALTIME => SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+        1..=3 => caller.data().wasi.start.elapsed().as_nanos(),
+        _ => return Ok(INVAL),
+    };
+    write_value(&mut caller, time, (nanos as u64).to_le_bytes())
+}
+
+fn random_get(mut caller: Caller<HostState>, buf: u32, len: u32) -> wasmtime::Result<u32> {
+    let mem = get_memory(&mut caller)?;
+    let (data, state) = mem.data_and_store_mut(&mut caller);
+    let Some(dest) = checked_memory_mut(state.strict, data, buf, len as usize)? else {
+        return Ok(FAULT);
+    };
+    for chunk in dest.chunks_mut(8) {
+        let random = state.wasi.next_random().to_le_bytes();
+        chunk.copy_from_slice(&random[..chunk.len()]);
+    }
+    Ok(SUCCESS)
+}
+
+fn proc_exit(code: u32) -> wasmtime::Result<()> {
+    Err(CartExit(code).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasm::{CartConfig, CartError, WasmCart};
+
+    /// Runs a cart's `update`, which imports `$func` from WASI with `signature` and puts what it
+    /// returns in the `errno` global
+    fn run(func: &str, signature: &str, strict: bool, body: &str) -> (WasmCart, u32) {
+        let wat = format!(
+            r#"(module
+                (import "{MODULE}" "{func}" (func ${func} {signature}))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "hello")
+                (global $errno (export "errno") (mut i32) (i32.const -1))
+                (func (export "update") {body}))"#
+        );
+        let config = CartConfig {
+            strict,
+            budget: None,
+            cache_dir: None,
+            ..CartConfig::default()
+        };
+        let mut cart = WasmCart::from_wat(&wat, config).unwrap();
+        cart.update().unwrap();
+        let errno = cart.instance.get_global(&mut cart.store, "errno").unwrap();
+        let errno = errno.get(&mut cart.store).unwrap_i32() as u32;
+        (cart, errno)
+    }
+
+    fn read_u64(cart: &mut WasmCart, ptr: usize) -> u64 {
+        let memory = cart.memory().unwrap().data(&cart.store);
+        u64::from_le_bytes(memory[ptr..ptr + 8].try_into().unwrap())
+    }
+
+    const FD_WRITE: &str = "(param i32 i32 i32 i32) (result i32)";
+
+    #[test]
+    fn fd_write_to_stdout() {
+        // One iovec at 0 for the 5 bytes at 16, how many were written goes at 8
+        let (mut cart, errno) = run(
+            "fd_write",
+            FD_WRITE,
+            true,
+            "(i32.store (i32.const 0) (i32.const 16))
+            (i32.store (i32.const 4) (i32.const 5))
+            (global.set $errno (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))",
+        );
+        assert_eq!(errno, SUCCESS);
+        assert_eq!(read_u64(&mut cart, 8) as u32, 5);
+    }
+
+    #[test]
+    fn fd_write_to_other_files() {
+        let (_, errno) = run(
+            "fd_write",
+            FD_WRITE,
+            true,
+            "(global.set $errno (call $fd_write (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 8)))",
+        );
+        assert_eq!(errno, BADF);
+    }
+
+    #[test]
+    fn proc_exit_stops_the_cart() {
+        let wat = format!(
+            r#"(module
+                (import "{MODULE}" "proc_exit" (func $proc_exit (param i32)))
+                (func (export "update") (call $proc_exit (i32.const 3)) unreachable))"#
+        );
+        let mut cart = WasmCart::from_wat(&wat, CartConfig::default()).unwrap();
+        match cart.update() {
+            Err(CartError::Exit {
+                export: "update",
+                code: 3,
+            }) => {}
+            result => panic!("expected the cart to exit, got {result:?}"),
+        }
+    }
+
+    const RANDOM_GET: &str = "(param i32 i32) (result i32)";
+
+    #[test]
+    fn random_get_fills_the_buffer() {
+        let (mut cart, errno) = run(
+            "random_get",
+            RANDOM_GET,
+            true,
+            "(global.set $errno (call $random_get (i32.const 32) (i32.const 12)))",
+        );
+        assert_eq!(errno, SUCCESS);
+        let memory = cart.memory().unwrap().data(&cart.store);
+        assert!(memory[32..44].iter().any(|&byte| byte != 0));
+        assert!(memory[44..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn random_get_out_of_bounds() {
+        let body = "(global.set $errno (call $random_get (i32.const 65530) (i32.const 16)))";
+        let (_, errno) = run("random_get", RANDOM_GET, false, body);
+        assert_eq!(errno, FAULT);
+    }
+
+    #[test]
+    fn clock_time_get() {
+        let signature = "(param i32 i64 i32) (result i32)";
+        let get = |clock: u32| {
+            format!(
+                "(global.set $errno (call $clock_time_get (i32.const {clock}) (i64.const 0) (i32.const 0)))"
+            )
+        };
+
+        let (mut cart, errno) = run("clock_time_get", signature, true, &get(CLOCK_REALTIME));
+        assert_eq!(errno, SUCCESS);
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+        let realtime = read_u64(&mut cart, 0);
+        assert!(realtime <= now && now - realtime < 60_000_000_000);
+
+        // The monotonic clock counts from when the cart was loaded
+        let (mut cart, errno) = run("clock_time_get", signature, true, &get(1));
+        assert_eq!(errno, SUCCESS);
+        assert!(read_u64(&mut cart, 0) < 60_000_000_000);
+
+        let (_, errno) = run("clock_time_get", signature, true, &get(4));
+        assert_eq!(errno, INVAL);
+    }
+}
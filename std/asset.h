@@ -0,0 +1,25 @@
+// Generated from the host API in src/wasm.rs by `abi write`, don't edit it by hand.
+#pragma once
+
+#include <common_types.h>
+#include <defines.h>
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// Assets are named blobs of data packaged with the cart.
+
+// Returns the size of the asset, or -1 if there isn't one called name.
+WASM("asset", "size")
+extern int32_t asset_Size(const char *name);
+
+// Copies up to len bytes of the asset, starting at offset, returning how many were copied.
+WASM("asset", "read")
+extern uint32_t asset_Read(const char *name, uint32_t offset, void *data, uint32_t len);
+
+#ifdef __cplusplus
+}
+#endif
@@ -1,4 +1,10 @@
+// Generated from the host API in src/wasm.rs by `abi write`, don't edit it by hand.
+#pragma once
+
+#include <common_types.h>
 #include <defines.h>
+#include <stdbool.h>
+#include <stdint.h>
 
 #ifdef __cplusplus
 extern "C" {
@@ -11,7 +17,7 @@ WASM("dbg", "end_line")
 extern void dbg_EndLine();
 
 WASM("dbg", "write_str")
-extern void dbg_WriteString(char *c);
+extern void dbg_WriteString(const char *s);
 
 WASM("dbg", "write_int")
 extern void dbg_WriteInt(int64_t i);
@@ -20,7 +26,7 @@ WASM("dbg", "write_uint")
 extern void dbg_WriteUint(uint64_t i);
 
 WASM("dbg", "write_ptr")
-extern void dbg_WritePtr(void *p);
+extern void dbg_WritePtr(const void *p);
 
 #ifdef __cplusplus
 }
@@ -1,6 +1,9 @@
+// Generated from the host API in src/wasm.rs by `abi write`, don't edit it by hand.
 #pragma once
 
+#include <common_types.h>
 #include <defines.h>
+#include <stdbool.h>
 #include <stdint.h>
 
 #ifdef __cplusplus
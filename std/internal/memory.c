@@ -26,7 +26,7 @@ inline uint32_t memory_size() {
     return value;
 }
 
-// Grows the memory by amount
+// Grows the memory by amount, returns the old number of pages, or -1 if it can't grow
 inline uint32_t memory_grow(uint32_t amount) asm("std_internal_memory_grow");
 inline uint32_t memory_grow(uint32_t amount) {
     uint32_t value;
@@ -43,7 +43,9 @@ inline uint32_t memory_grow(uint32_t amount) {
 memoryblock_t *reserve_page() asm("std_internal_memory_reserve_page");
 memoryblock_t *reserve_page() {
     memoryblock_t *ptr = (memoryblock_t *)(memory_size() * PAGE_SIZE);
-    memory_grow(1);
+    // Out of memory
+    if (memory_grow(1) == (uint32_t)-1)
+        return 0;
 
     *ptr = (memoryblock_t) {
         .next = 0,
@@ -59,6 +61,8 @@ memoryblock_t *next_free(size_t size) {
     // Not initialized
     if (!heap_pointer)
         heap_pointer = reserve_page();
+    if (!heap_pointer)
+        return 0;
 
     // Calculate the actual needed size
     size_t acutal_size = size + sizeof(memoryblock_t);
@@ -74,6 +78,8 @@ memoryblock_t *next_free(size_t size) {
         // If it doesn't have a next, create a new one
         if (!curr->next) {
             memoryblock_t *next = reserve_page();
+            if (!next)
+                return 0;
             // Merge the blocks if current is free
             if (curr->free) {
                 curr->size += next->size;
@@ -114,6 +120,8 @@ void merge_blocks() {
 void *malloc(size_t size) {
     // Get the next free block
     memoryblock_t *block = next_free(size);
+    if (!block)
+        return 0;
     void *v_block = block;
 
     // Get the left over bytes
@@ -9,6 +9,7 @@ extern "C" {
 typedef unsigned long size_t;
 typedef signed long ptrdiff_t;
 
+// Returns 0 when the console is out of memory
 void *malloc(size_t size);
 
 void free(void *ptr);
@@ -0,0 +1,15 @@
+#pragma once
+
+// Describes the cart to the console, with a key=value pair on each line, e.g.
+//
+// CART_METADATA(
+//     "title=Test Game\n"
+//     "author=Someone\n"
+//     "api_version=1\n"
+//     "gamepads=2\n"
+// )
+//
+// It's put in the module's cart_metadata custom section, so it can only be used once.
+#define CART_METADATA(text)                                                                        \
+    __attribute__((used, section(".custom_section.cart_metadata")))                               \
+    static const char cart_metadata[] = text;
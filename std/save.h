@@ -0,0 +1,26 @@
+// Generated from the host API in src/wasm.rs by `abi write`, don't edit it by hand.
+#pragma once
+
+#include <common_types.h>
+#include <defines.h>
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// Save data persists between runs of the cart, it's save_Size() bytes long.
+
+WASM("save", "size")
+extern uint32_t save_Size();
+
+WASM("save", "read")
+extern void save_Read(uint32_t offset, void *data, uint32_t len);
+
+WASM("save", "write")
+extern void save_Write(uint32_t offset, const void *data, uint32_t len);
+
+#ifdef __cplusplus
+}
+#endif
@@ -1,9 +1,11 @@
+// Generated from the host API in src/wasm.rs by `abi write`, don't edit it by hand.
 #pragma once
 
 #include <common_types.h>
 #include <defines.h>
-#include <stdint.h>
 #include <stdbool.h>
+#include <stdint.h>
+#include <tile.h>
 
 #ifdef __cplusplus
 extern "C" {
@@ -16,7 +18,7 @@ WASM("sprite", "set_sprite_visible")
 extern void sprite_Set_Visible(uint8_t sprite, bool visible);
 
 WASM("sprite", "set_sprite_palette")
-extern void sprite_Set_Palette(uint8_t sprite, uint8_t tile);
+extern void sprite_Set_Palette(uint8_t sprite, uint8_t palette);
 
 WASM("sprite", "get_sprite_position")
 extern position_s16_t sprite_Get_Position(uint8_t sprite);
@@ -33,6 +35,29 @@ extern void sprite_Set_Position(uint8_t sprite, position_s16_t pos);
 WASM("sprite", "set_sprite_flip")
 extern void sprite_Set_Flip(uint8_t sprite, bool flip_x, bool flip_y);
 
+// Puts the sprite behind the first priority backgrounds, 0 is in front of all of them
+WASM("sprite", "set_sprite_priority")
+extern void sprite_Set_Priority(uint8_t sprite, uint8_t priority);
+
+// Sets the sprite's size in pixels, each side is 8, 16, 32 or 64. Sprites bigger than a
+// tile are made of consecutive tiles, a row at a time.
+WASM("sprite", "set_sprite_size")
+extern void sprite_Set_Size(uint8_t sprite, uint8_t width, uint8_t height);
+
+// Sets one of the matrices affine sprites share, each entry is 8.8 fixed point. It
+// transforms the screen into the sprite, around the sprite's center.
+WASM("sprite", "write_sprite_matrix")
+extern void sprite_WriteMatrix(uint8_t matrix, int16_t a, int16_t b, int16_t c, int16_t d);
+
+// Rotates and scales the sprite with a shared matrix, or draws it normally if matrix is
+// -1. Double size sprites are drawn in a box twice as big, so they aren't cut off.
+WASM("sprite", "set_sprite_matrix")
+extern void sprite_Set_Matrix(uint8_t sprite, int32_t matrix, bool double_size);
+
+// Sets how the sprite's pixels blend with what's under them
+WASM("sprite", "set_sprite_blend")
+extern void sprite_Set_Blend(uint8_t sprite, blendmode_t mode);
+
 #ifdef __cplusplus
 }
 #endif
@@ -0,0 +1,54 @@
+// Generated from the host API in src/wasm.rs by `abi write`, don't edit it by hand.
+#pragma once
+
+#include <common_types.h>
+#include <defines.h>
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// The API version these headers are for, put it in the cart's metadata as api_version
+#define API_VERSION 7
+
+#define FEATURE_SAVE_DATA 1
+#define FEATURE_ASSETS 2
+#define FEATURE_HOOKS 4
+#define FEATURE_WASI 8
+
+WASM("system", "api_version")
+extern uint32_t system_ApiVersion();
+
+// Which FEATURE_ flags the console supports
+WASM("system", "features")
+extern uint32_t system_Features();
+
+WASM("system", "screen_width")
+extern uint32_t system_ScreenWidth();
+
+WASM("system", "screen_height")
+extern uint32_t system_ScreenHeight();
+
+WASM("system", "sprite_count")
+extern uint32_t system_SpriteCount();
+
+WASM("system", "background_count")
+extern uint32_t system_BackgroundCount();
+
+WASM("system", "tile_count")
+extern uint32_t system_TileCount();
+
+WASM("system", "palette_size")
+extern uint32_t system_PaletteSize();
+
+WASM("system", "gamepad_count")
+extern uint32_t system_GamepadCount();
+
+WASM("system", "sprite_matrix_count")
+extern uint32_t system_SpriteMatrixCount();
+
+#ifdef __cplusplus
+}
+#endif
@@ -1,14 +1,34 @@
+// Generated from the host API in src/wasm.rs by `abi write`, don't edit it by hand.
 #pragma once
 
 #include <common_types.h>
 #include <defines.h>
-#include <stdint.h>
 #include <stdbool.h>
+#include <stdint.h>
 
 #ifdef __cplusplus
 extern "C" {
 #endif
 
+typedef enum {
+    Blend_None = 0,
+    Blend_Alpha = 1,
+    Blend_Add = 2,
+    Blend_Subtract = 3,
+    Blend_Half = 4
+} blendmode_t;
+
+#define BLEND_TARGET_BACKGROUND_0 1
+#define BLEND_TARGET_BACKGROUND_1 2
+#define BLEND_TARGET_BACKGROUND_2 4
+#define BLEND_TARGET_BACKGROUND_3 8
+#define BLEND_TARGET_BACKGROUND_4 16
+#define BLEND_TARGET_BACKGROUND_5 32
+#define BLEND_TARGET_BACKGROUND_6 64
+#define BLEND_TARGET_BACKGROUND_7 128
+#define BLEND_TARGET_SPRITES 256
+#define BLEND_TARGET_BACKDROP 512
+
 WASM("tile", "write_palette")
 extern void tile_WritePalette(uint8_t palette, uint32_t color);
 
@@ -18,18 +38,36 @@ extern void tile_WriteTile(uint8_t tile_number, const uint8_t *tile);
 WASM("tile", "set_background_tile")
 extern void tile_Set_BackgroundTile(uint8_t background, position_u8_t pos, uint8_t tile);
 
-
 WASM("tile", "set_background_visible")
 extern void tile_Set_BackgroundVisible(uint8_t background, bool visible);
 
+// Sets how the background's pixels blend with what's under them
+WASM("tile", "set_background_blend")
+extern void tile_Set_BackgroundBlend(uint8_t background, blendmode_t mode);
+
+// Which layers, as BLEND_TARGET_ flags, blending pixels blend with. Pixels over any
+// other layer are drawn as they are.
+WASM("tile", "set_blend_targets")
+extern void tile_Set_BlendTargets(uint32_t targets);
+
+// How much of a pixel and what's under it Blend_Alpha takes, each out of 16
+WASM("tile", "set_blend_alpha")
+extern void tile_Set_BlendAlpha(uint8_t top, uint8_t below);
 
+// Sets the palette entry drawn where nothing else is
 WASM("tile", "set_background_palette")
 extern void tile_Set_BackgroundPalette(uint8_t palette);
 
-
 WASM("tile", "set_background_tile_palette")
 extern void tile_Set_BackgroundTilePalette(uint8_t background, position_u8_t pos, uint8_t palette);
 
+// Mirrors the tile at a position in a background
+WASM("tile", "set_background_tile_flip")
+extern void tile_Set_BackgroundTileFlip(uint8_t background, position_u8_t pos, bool flip_x, bool flip_y);
+
+// Draws the tile at a position in a background over sprites, whatever their priority
+WASM("tile", "set_background_tile_priority")
+extern void tile_Set_BackgroundTilePriority(uint8_t background, position_u8_t pos, bool above_sprites);
 
 WASM("tile", "set_background_scroll_pre")
 extern void tile_Set_BackgroundScroll_Pre(uint8_t background, position_s16_t pos);
@@ -40,7 +78,6 @@ extern void tile_Set_BackgroundScroll_Pre_X(uint8_t background, int16_t x);
 WASM("tile", "set_background_scroll_pre_y")
 extern void tile_Set_BackgroundScroll_Pre_Y(uint8_t background, int16_t y);
 
-
 WASM("tile", "get_background_scroll_pre")
 extern position_s16_t tile_Get_BackgroundScroll_Pre(uint8_t background);
 
@@ -50,7 +87,6 @@ extern int16_t tile_Get_BackgroundScroll_Pre_X(uint8_t background);
 WASM("tile", "get_background_scroll_pre_y")
 extern int16_t tile_Get_BackgroundScroll_Pre_Y(uint8_t background);
 
-
 WASM("tile", "set_background_scroll_post")
 extern void tile_Set_BackgroundScroll_Post(uint8_t background, position_s16_t pos);
 
@@ -60,7 +96,6 @@ extern void tile_Set_BackgroundScroll_Post_X(uint8_t background, int16_t x);
 WASM("tile", "set_background_scroll_post_y")
 extern void tile_Set_BackgroundScroll_Post_Y(uint8_t background, int16_t y);
 
-
 WASM("tile", "get_background_scroll_post")
 extern position_s16_t tile_Get_BackgroundScroll_Post(uint8_t background);
 
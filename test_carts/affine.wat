@@ -0,0 +1,45 @@
+;; Affine sprites of the corner tile: a quarter turn, 45 degrees in a double size box so its
+;; corners aren't cut off, half size, and mirrored on x.
+(module
+    (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+    (import "tile" "write_tile" (func $write_tile (param i32 i32)))
+    (import "tile" "set_background_palette" (func $set_background_palette (param i32)))
+    (import "sprite" "set_sprite_tile" (func $set_sprite_tile (param i32 i32)))
+    (import "sprite" "set_sprite_visible" (func $set_sprite_visible (param i32 i32)))
+    (import "sprite" "set_sprite_palette" (func $set_sprite_palette (param i32 i32)))
+    (import "sprite" "set_sprite_position" (func $set_sprite_position (param i32 i32 i32)))
+    (import "sprite" "set_sprite_size" (func $set_sprite_size (param i32 i32 i32)))
+    (import "sprite" "write_sprite_matrix" (func $write_sprite_matrix (param i32 i32 i32 i32 i32)))
+    (import "sprite" "set_sprite_matrix" (func $set_sprite_matrix (param i32 i32 i32)))
+
+    (memory (export "memory") 1)
+    ;; Tile 1, lines along the top and left edges and a diagonal, so it's asymmetric on both axes
+    (data (i32.const 0) "\11\11\11\11\11\11\11\11\10\22\00\00\00\00\00\00\10\00\22\00\00\00\00\00\10\00\00\22\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00")
+
+    (func (export "init") (local $i i32)
+        (call $write_palette (i32.const 0) (i32.const 0xff4040))
+        (call $write_palette (i32.const 1) (i32.const 0x40ff40))
+        (call $write_palette (i32.const 2) (i32.const 0x202020))
+        (call $set_background_palette (i32.const 2))
+        (call $write_tile (i32.const 1) (i32.const 0))
+
+        (call $write_sprite_matrix (i32.const 0) (i32.const 0) (i32.const 256) (i32.const -256) (i32.const 0))
+        (call $write_sprite_matrix (i32.const 1) (i32.const 181) (i32.const 181) (i32.const -181) (i32.const 181))
+        (call $write_sprite_matrix (i32.const 2) (i32.const 512) (i32.const 0) (i32.const 0) (i32.const 512))
+        (call $write_sprite_matrix (i32.const 3) (i32.const -256) (i32.const 0) (i32.const 0) (i32.const 256))
+
+        ;; Sprite i uses matrix i
+        (loop $sprites
+            (call $set_sprite_tile (local.get $i) (i32.const 1))
+            (call $set_sprite_palette (local.get $i) (i32.const 0))
+            (call $set_sprite_visible (local.get $i) (i32.const 1))
+            (call $set_sprite_size (local.get $i) (i32.const 32) (i32.const 32))
+            (call $set_sprite_position (local.get $i)
+                (i32.add (i32.const 16) (i32.mul (local.get $i) (i32.const 48)))
+                (i32.const 32))
+            (call $set_sprite_matrix (local.get $i) (local.get $i) (i32.eq (local.get $i) (i32.const 1)))
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br_if $sprites (i32.lt_u (local.get $i) (i32.const 4)))))
+
+    (func (export "update"))
+    (func (export "v_blank") (param i32)))
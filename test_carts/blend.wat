@@ -0,0 +1,68 @@
+;; A grey floor on background 1 under a band of translucent blue water on background 0, and a
+;; sprite for each blend mode on the floor and half under the water, which blends with it. The
+;; backdrop isn't a target, so the last sprite is drawn as it is where it's over it.
+(module
+    (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+    (import "tile" "write_tile" (func $write_tile (param i32 i32)))
+    (import "tile" "set_background_tile" (func $set_background_tile (param i32 i32 i32 i32)))
+    (import "tile" "set_background_tile_palette" (func $set_background_tile_palette (param i32 i32 i32 i32)))
+    (import "tile" "set_background_palette" (func $set_background_palette (param i32)))
+    (import "tile" "set_background_blend" (func $set_background_blend (param i32 i32)))
+    (import "tile" "set_blend_targets" (func $set_blend_targets (param i32)))
+    (import "tile" "set_blend_alpha" (func $set_blend_alpha (param i32 i32)))
+    (import "sprite" "set_sprite_tile" (func $set_sprite_tile (param i32 i32)))
+    (import "sprite" "set_sprite_visible" (func $set_sprite_visible (param i32 i32)))
+    (import "sprite" "set_sprite_palette" (func $set_sprite_palette (param i32 i32)))
+    (import "sprite" "set_sprite_position" (func $set_sprite_position (param i32 i32 i32)))
+    (import "sprite" "set_sprite_priority" (func $set_sprite_priority (param i32 i32)))
+    (import "sprite" "set_sprite_size" (func $set_sprite_size (param i32 i32 i32)))
+    (import "sprite" "set_sprite_blend" (func $set_sprite_blend (param i32 i32)))
+
+    (memory (export "memory") 1)
+    ;; Tile 1, solid
+    (data (i32.const 0) "\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11")
+
+    (func (export "init") (local $x i32) (local $y i32) (local $i i32)
+        (call $write_palette (i32.const 0) (i32.const 0xff4040))
+        (call $write_palette (i32.const 1) (i32.const 0x4040ff))
+        (call $write_palette (i32.const 2) (i32.const 0x808080))
+        (call $write_palette (i32.const 3) (i32.const 0x202020))
+        (call $set_background_palette (i32.const 3))
+        (call $write_tile (i32.const 1) (i32.const 0))
+
+        (loop $columns
+            (local.set $y (i32.const 2))
+            (loop $rows
+                (call $set_background_tile (i32.const 1) (local.get $x) (local.get $y) (i32.const 1))
+                (call $set_background_tile_palette (i32.const 1) (local.get $x) (local.get $y) (i32.const 2))
+                (if (i32.and (i32.ge_u (local.get $y) (i32.const 4)) (i32.lt_u (local.get $y) (i32.const 6)))
+                    (then
+                        (call $set_background_tile (i32.const 0) (local.get $x) (local.get $y) (i32.const 1))
+                        (call $set_background_tile_palette (i32.const 0) (local.get $x) (local.get $y) (i32.const 1))))
+                (local.set $y (i32.add (local.get $y) (i32.const 1)))
+                (br_if $rows (i32.lt_u (local.get $y) (i32.const 12))))
+            (local.set $x (i32.add (local.get $x) (i32.const 1)))
+            (br_if $columns (i32.lt_u (local.get $x) (i32.const 20))))
+        ;; Alpha
+        (call $set_background_blend (i32.const 0) (i32.const 1))
+        (call $set_blend_alpha (i32.const 10) (i32.const 6))
+
+        ;; Sprite i blends with mode i
+        (loop $sprites
+            (call $set_sprite_tile (local.get $i) (i32.const 1))
+            (call $set_sprite_palette (local.get $i) (i32.const 0))
+            (call $set_sprite_visible (local.get $i) (i32.const 1))
+            (call $set_sprite_priority (local.get $i) (i32.const 1))
+            (call $set_sprite_size (local.get $i) (i32.const 32) (i32.const 32))
+            (call $set_sprite_position (local.get $i)
+                (i32.add (i32.const 16) (i32.mul (local.get $i) (i32.const 48)))
+                (i32.const 48))
+            (call $set_sprite_blend (local.get $i) (local.get $i))
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br_if $sprites (i32.lt_u (local.get $i) (i32.const 5))))
+        (call $set_sprite_position (i32.const 4) (i32.const 208) (i32.const 16))
+        ;; Background 1 and sprites
+        (call $set_blend_targets (i32.const 258)))
+
+    (func (export "update"))
+    (func (export "v_blank") (param i32)))
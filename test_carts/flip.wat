@@ -0,0 +1,51 @@
+;; Each sprite shows the same corner tile with a different combination of flips, and they move
+;; down while Down is held.
+(module
+    (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+    (import "tile" "write_tile" (func $write_tile (param i32 i32)))
+    (import "tile" "set_background_palette" (func $set_background_palette (param i32)))
+    (import "sprite" "set_sprite_tile" (func $set_sprite_tile (param i32 i32)))
+    (import "sprite" "set_sprite_visible" (func $set_sprite_visible (param i32 i32)))
+    (import "sprite" "set_sprite_palette" (func $set_sprite_palette (param i32 i32)))
+    (import "sprite" "set_sprite_position" (func $set_sprite_position (param i32 i32 i32)))
+    (import "sprite" "get_sprite_position_x" (func $get_sprite_position_x (param i32) (result i32)))
+    (import "sprite" "get_sprite_position_y" (func $get_sprite_position_y (param i32) (result i32)))
+    (import "sprite" "set_sprite_flip" (func $set_sprite_flip (param i32 i32 i32)))
+    (import "gamepad" "get_state" (func $get_state (param i32) (result i32)))
+
+    (memory (export "memory") 1)
+    ;; Tile 1, lines along the top and left edges and a diagonal, so it's asymmetric on both axes
+    (data (i32.const 0) "\11\11\11\11\11\11\11\11\10\22\00\00\00\00\00\00\10\00\22\00\00\00\00\00\10\00\00\22\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00")
+
+    (func (export "init") (local $i i32)
+        (call $write_palette (i32.const 0) (i32.const 0xff4040))
+        (call $write_palette (i32.const 1) (i32.const 0x40ff40))
+        (call $write_palette (i32.const 2) (i32.const 0x202020))
+        (call $set_background_palette (i32.const 2))
+        (call $write_tile (i32.const 1) (i32.const 0))
+
+        ;; Sprite i is flipped on x when bit 0 is set, and on y when bit 1 is set
+        (loop $sprites
+            (call $set_sprite_tile (local.get $i) (i32.const 1))
+            (call $set_sprite_palette (local.get $i) (i32.const 0))
+            (call $set_sprite_visible (local.get $i) (i32.const 1))
+            (call $set_sprite_position (local.get $i)
+                (i32.add (i32.const 32) (i32.mul (local.get $i) (i32.const 32)))
+                (i32.const 32))
+            (call $set_sprite_flip (local.get $i)
+                (i32.and (local.get $i) (i32.const 1))
+                (i32.ne (i32.and (local.get $i) (i32.const 2)) (i32.const 0)))
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br_if $sprites (i32.lt_u (local.get $i) (i32.const 4)))))
+
+    (func (export "update") (local $i i32)
+        ;; Down on the first gamepad
+        (if (i32.and (call $get_state (i32.const 0)) (i32.const 2))
+            (then
+                (loop $sprites
+                    (call $set_sprite_position (local.get $i)
+                        (call $get_sprite_position_x (local.get $i))
+                        (i32.add (call $get_sprite_position_y (local.get $i)) (i32.const 1)))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br_if $sprites (i32.lt_u (local.get $i) (i32.const 4)))))))
+    (func (export "v_blank") (param i32)))
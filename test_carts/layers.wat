@@ -0,0 +1,65 @@
+;; Written in wasm text so its golden image can be checked without a C toolchain.
+;; A scrolled background with a striped block, a sprite the gamepad moves right, and a
+;; half-blended sprite over both.
+(module
+    (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+    (import "tile" "write_tile" (func $write_tile (param i32 i32)))
+    (import "tile" "set_background_tile" (func $set_background_tile (param i32 i32 i32 i32)))
+    (import "tile" "set_background_tile_palette" (func $set_background_tile_palette (param i32 i32 i32 i32)))
+    (import "tile" "set_background_palette" (func $set_background_palette (param i32)))
+    (import "tile" "set_background_scroll_pre" (func $set_background_scroll_pre (param i32 i32 i32)))
+    (import "sprite" "set_sprite_tile" (func $set_sprite_tile (param i32 i32)))
+    (import "sprite" "set_sprite_visible" (func $set_sprite_visible (param i32 i32)))
+    (import "sprite" "set_sprite_palette" (func $set_sprite_palette (param i32 i32)))
+    (import "sprite" "set_sprite_position" (func $set_sprite_position (param i32 i32 i32)))
+    (import "sprite" "set_sprite_size" (func $set_sprite_size (param i32 i32 i32)))
+    (import "sprite" "set_sprite_blend" (func $set_sprite_blend (param i32 i32)))
+    (import "gamepad" "get_state" (func $get_state (param i32) (result i32)))
+
+    (memory (export "memory") 1)
+    ;; Tile 1, stripes of palette offsets 1 and 2
+    (data (i32.const 0) "\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12\12")
+    ;; Tile 2, solid on top with every other column see-through below
+    (data (i32.const 128) "\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\33\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30\30")
+
+    (global $x (mut i32) (i32.const 40))
+
+    (func (export "init") (local $cx i32) (local $cy i32)
+        (call $write_palette (i32.const 0) (i32.const 0x203040))
+        (call $write_palette (i32.const 1) (i32.const 0xff4040))
+        (call $write_palette (i32.const 2) (i32.const 0x40c040))
+        (call $write_palette (i32.const 3) (i32.const 0xf0f0f0))
+        (call $set_background_palette (i32.const 0))
+        (call $write_tile (i32.const 1) (i32.const 0))
+        (call $write_tile (i32.const 2) (i32.const 128))
+
+        ;; A 10 by 6 block of stripes, scrolled so it doesn't line up with the cells
+        (local.set $cy (i32.const 2))
+        (loop $rows
+            (local.set $cx (i32.const 2))
+            (loop $columns
+                (call $set_background_tile (i32.const 0) (local.get $cx) (local.get $cy) (i32.const 1))
+                (call $set_background_tile_palette (i32.const 0) (local.get $cx) (local.get $cy) (i32.const 1))
+                (local.set $cx (i32.add (local.get $cx) (i32.const 1)))
+                (br_if $columns (i32.lt_u (local.get $cx) (i32.const 12))))
+            (local.set $cy (i32.add (local.get $cy) (i32.const 1)))
+            (br_if $rows (i32.lt_u (local.get $cy) (i32.const 8))))
+        (call $set_background_scroll_pre (i32.const 0) (i32.const 3) (i32.const 5))
+
+        (call $set_sprite_tile (i32.const 0) (i32.const 2))
+        (call $set_sprite_palette (i32.const 0) (i32.const 1))
+        (call $set_sprite_visible (i32.const 0) (i32.const 1))
+
+        ;; Half blended, 32 by 16 pixels from tiles 1 and 2
+        (call $set_sprite_tile (i32.const 1) (i32.const 1))
+        (call $set_sprite_palette (i32.const 1) (i32.const 2))
+        (call $set_sprite_size (i32.const 1) (i32.const 32) (i32.const 16))
+        (call $set_sprite_blend (i32.const 1) (i32.const 4))
+        (call $set_sprite_position (i32.const 1) (i32.const 60) (i32.const 50))
+        (call $set_sprite_visible (i32.const 1) (i32.const 1)))
+
+    (func (export "update")
+        ;; Right on the first gamepad
+        (if (i32.and (call $get_state (i32.const 0)) (i32.const 8))
+            (then (global.set $x (i32.add (global.get $x) (i32.const 1)))))
+        (call $set_sprite_position (i32.const 0) (global.get $x) (i32.const 100))))
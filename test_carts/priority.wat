@@ -0,0 +1,55 @@
+;; Striped foliage on background 0 in front of a solid wall on background 1, and a sprite at each
+;; priority: in front of everything, between the foliage and the wall, then hidden behind both.
+(module
+    (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+    (import "tile" "write_tile" (func $write_tile (param i32 i32)))
+    (import "tile" "set_background_tile" (func $set_background_tile (param i32 i32 i32 i32)))
+    (import "tile" "set_background_tile_palette" (func $set_background_tile_palette (param i32 i32 i32 i32)))
+    (import "tile" "set_background_palette" (func $set_background_palette (param i32)))
+    (import "sprite" "set_sprite_tile" (func $set_sprite_tile (param i32 i32)))
+    (import "sprite" "set_sprite_visible" (func $set_sprite_visible (param i32 i32)))
+    (import "sprite" "set_sprite_palette" (func $set_sprite_palette (param i32 i32)))
+    (import "sprite" "set_sprite_position" (func $set_sprite_position (param i32 i32 i32)))
+    (import "sprite" "set_sprite_priority" (func $set_sprite_priority (param i32 i32)))
+
+    (memory (export "memory") 1)
+    ;; Tile 1, solid
+    (data (i32.const 0) "\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11")
+    ;; Tile 2, every other column, so whatever's behind shows through
+    (data (i32.const 128) "\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10\10")
+
+    (func (export "init") (local $x i32) (local $y i32) (local $i i32)
+        (call $write_palette (i32.const 0) (i32.const 0xff4040))
+        (call $write_palette (i32.const 1) (i32.const 0x40ff40))
+        (call $write_palette (i32.const 2) (i32.const 0x4040ff))
+        (call $write_palette (i32.const 3) (i32.const 0x202020))
+        (call $set_background_palette (i32.const 3))
+        (call $write_tile (i32.const 1) (i32.const 0))
+        (call $write_tile (i32.const 2) (i32.const 128))
+
+        (loop $columns
+            (call $set_background_tile (i32.const 0) (local.get $x) (i32.const 2) (i32.const 2))
+            (call $set_background_tile_palette (i32.const 0) (local.get $x) (i32.const 2) (i32.const 1))
+            (local.set $y (i32.const 1))
+            (loop $rows
+                (call $set_background_tile (i32.const 1) (local.get $x) (local.get $y) (i32.const 1))
+                (call $set_background_tile_palette (i32.const 1) (local.get $x) (local.get $y) (i32.const 2))
+                (local.set $y (i32.add (local.get $y) (i32.const 1)))
+                (br_if $rows (i32.lt_u (local.get $y) (i32.const 4))))
+            (local.set $x (i32.add (local.get $x) (i32.const 1)))
+            (br_if $columns (i32.lt_u (local.get $x) (i32.const 16))))
+
+        ;; Sprite i is behind the first i backgrounds
+        (loop $sprites
+            (call $set_sprite_tile (local.get $i) (i32.const 1))
+            (call $set_sprite_palette (local.get $i) (i32.const 0))
+            (call $set_sprite_visible (local.get $i) (i32.const 1))
+            (call $set_sprite_position (local.get $i)
+                (i32.add (i32.const 32) (i32.mul (local.get $i) (i32.const 32)))
+                (i32.const 40))
+            (call $set_sprite_priority (local.get $i) (local.get $i))
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br_if $sprites (i32.lt_u (local.get $i) (i32.const 3)))))
+
+    (func (export "update"))
+    (func (export "v_blank") (param i32)))
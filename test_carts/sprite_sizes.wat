@@ -0,0 +1,44 @@
+;; Sprites of each size, made of tiles that are each visibly different, and a flipped one so the
+;; tiles are seen to be mirrored as a whole.
+(module
+    (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+    (import "tile" "write_tile" (func $write_tile (param i32 i32)))
+    (import "tile" "set_background_palette" (func $set_background_palette (param i32)))
+    (import "sprite" "set_sprite_tile" (func $set_sprite_tile (param i32 i32)))
+    (import "sprite" "set_sprite_visible" (func $set_sprite_visible (param i32 i32)))
+    (import "sprite" "set_sprite_palette" (func $set_sprite_palette (param i32 i32)))
+    (import "sprite" "set_sprite_position" (func $set_sprite_position (param i32 i32 i32)))
+    (import "sprite" "set_sprite_size" (func $set_sprite_size (param i32 i32 i32)))
+    (import "sprite" "set_sprite_flip" (func $set_sprite_flip (param i32 i32 i32)))
+
+    (memory (export "memory") 1)
+    ;; Tiles 1 to 16, solid with a border, cycling through the first four colors
+    (data (i32.const 0) "\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\12\12\12\12\12\12\12\12\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\12\12\12\12\12\12\12\13\13\13\13\13\13\13\13\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\13\13\13\13\13\13\13\14\14\14\14\14\14\14\14\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\14\14\14\14\14\14\14\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\12\12\12\12\12\12\12\12\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\12\12\12\12\12\12\12\13\13\13\13\13\13\13\13\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\13\13\13\13\13\13\13\14\14\14\14\14\14\14\14\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\14\14\14\14\14\14\14\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\12\12\12\12\12\12\12\12\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\12\12\12\12\12\12\12\13\13\13\13\13\13\13\13\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\13\13\13\13\13\13\13\14\14\14\14\14\14\14\14\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\14\14\14\14\14\14\14\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\12\12\12\12\12\12\12\12\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\22\22\22\22\22\22\22\12\12\12\12\12\12\12\12\13\13\13\13\13\13\13\13\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\33\33\33\33\33\33\33\13\13\13\13\13\13\13\13\14\14\14\14\14\14\14\14\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\44\44\44\44\44\44\44\14\14\14\14\14\14\14\14")
+
+    (func $sprite (param $i i32) (param $width i32) (param $height i32) (param $x i32)
+        (call $set_sprite_tile (local.get $i) (i32.const 1))
+        (call $set_sprite_palette (local.get $i) (i32.const 0))
+        (call $set_sprite_visible (local.get $i) (i32.const 1))
+        (call $set_sprite_size (local.get $i) (local.get $width) (local.get $height))
+        (call $set_sprite_position (local.get $i) (local.get $x) (i32.const 32)))
+
+    (func (export "init") (local $t i32)
+        (call $write_palette (i32.const 0) (i32.const 0xff4040))
+        (call $write_palette (i32.const 1) (i32.const 0x40ff40))
+        (call $write_palette (i32.const 2) (i32.const 0x4040ff))
+        (call $write_palette (i32.const 3) (i32.const 0xffff40))
+        (call $write_palette (i32.const 4) (i32.const 0x202020))
+        (call $set_background_palette (i32.const 4))
+        (loop $tiles
+            (call $write_tile (i32.add (local.get $t) (i32.const 1)) (i32.mul (local.get $t) (i32.const 128)))
+            (local.set $t (i32.add (local.get $t) (i32.const 1)))
+            (br_if $tiles (i32.lt_u (local.get $t) (i32.const 16))))
+
+        (call $sprite (i32.const 0) (i32.const 8) (i32.const 8) (i32.const 16))
+        (call $sprite (i32.const 1) (i32.const 16) (i32.const 32) (i32.const 40))
+        (call $sprite (i32.const 2) (i32.const 32) (i32.const 32) (i32.const 72))
+        (call $sprite (i32.const 3) (i32.const 64) (i32.const 64) (i32.const 120))
+        (call $set_sprite_flip (i32.const 3) (i32.const 1) (i32.const 0)))
+
+    (func (export "update"))
+    (func (export "v_blank") (param i32)))
@@ -0,0 +1,52 @@
+;; A row of cells showing the same corner tile with each combination of flips, and a sprite half
+;; covered by a cell drawn above sprites and half by one that isn't.
+(module
+    (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+    (import "tile" "write_tile" (func $write_tile (param i32 i32)))
+    (import "tile" "set_background_tile" (func $set_background_tile (param i32 i32 i32 i32)))
+    (import "tile" "set_background_tile_palette" (func $set_background_tile_palette (param i32 i32 i32 i32)))
+    (import "tile" "set_background_tile_flip" (func $set_background_tile_flip (param i32 i32 i32 i32 i32)))
+    (import "tile" "set_background_tile_priority" (func $set_background_tile_priority (param i32 i32 i32 i32)))
+    (import "tile" "set_background_palette" (func $set_background_palette (param i32)))
+    (import "sprite" "set_sprite_tile" (func $set_sprite_tile (param i32 i32)))
+    (import "sprite" "set_sprite_visible" (func $set_sprite_visible (param i32 i32)))
+    (import "sprite" "set_sprite_palette" (func $set_sprite_palette (param i32 i32)))
+    (import "sprite" "set_sprite_position" (func $set_sprite_position (param i32 i32 i32)))
+
+    (memory (export "memory") 1)
+    ;; Tile 1, lines along the top and left edges and a diagonal, so it's asymmetric on both axes
+    (data (i32.const 0) "\11\11\11\11\11\11\11\11\10\22\00\00\00\00\00\00\10\00\22\00\00\00\00\00\10\00\00\22\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00\10\00\00\00\00\00\00\00")
+    ;; Tile 2, solid
+    (data (i32.const 128) "\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11\11")
+
+    (func (export "init") (local $i i32) (local $x i32)
+        (call $write_palette (i32.const 0) (i32.const 0xff4040))
+        (call $write_palette (i32.const 1) (i32.const 0x40ff40))
+        (call $write_palette (i32.const 2) (i32.const 0x202020))
+        (call $set_background_palette (i32.const 2))
+        (call $write_tile (i32.const 1) (i32.const 0))
+        (call $write_tile (i32.const 2) (i32.const 128))
+
+        ;; Cell i is flipped on x when bit 0 is set, and on y when bit 1 is set
+        (loop $cells
+            (local.set $x (i32.add (i32.const 2) (i32.mul (local.get $i) (i32.const 2))))
+            (call $set_background_tile (i32.const 0) (local.get $x) (i32.const 2) (i32.const 1))
+            (call $set_background_tile_flip (i32.const 0) (local.get $x) (i32.const 2)
+                (i32.and (local.get $i) (i32.const 1))
+                (i32.ne (i32.and (local.get $i) (i32.const 2)) (i32.const 0)))
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br_if $cells (i32.lt_u (local.get $i) (i32.const 4))))
+
+        (call $set_background_tile (i32.const 0) (i32.const 2) (i32.const 4) (i32.const 2))
+        (call $set_background_tile_palette (i32.const 0) (i32.const 2) (i32.const 4) (i32.const 1))
+        (call $set_background_tile_priority (i32.const 0) (i32.const 2) (i32.const 4) (i32.const 1))
+        (call $set_background_tile (i32.const 0) (i32.const 3) (i32.const 4) (i32.const 2))
+        (call $set_background_tile_palette (i32.const 0) (i32.const 3) (i32.const 4) (i32.const 1))
+
+        (call $set_sprite_tile (i32.const 0) (i32.const 2))
+        (call $set_sprite_palette (i32.const 0) (i32.const 0))
+        (call $set_sprite_visible (i32.const 0) (i32.const 1))
+        (call $set_sprite_position (i32.const 0) (i32.const 40) (i32.const 64)))
+
+    (func (export "update"))
+    (func (export "v_blank") (param i32)))
@@ -0,0 +1,59 @@
+;; A rotated checkerboard under a mirrored, stretched grid that scrolls every frame and waves a
+;; little per scanline.
+(module
+    (import "tile" "write_palette" (func $write_palette (param i32 i32)))
+    (import "tile" "write_tile" (func $write_tile (param i32 i32)))
+    (import "tile" "set_background_tile" (func $set_background_tile (param i32 i32 i32 i32)))
+    (import "tile" "set_background_palette" (func $set_background_palette (param i32)))
+    (import "tile" "set_background_scroll_pre" (func $set_background_scroll_pre (param i32 i32 i32)))
+    (import "tile" "set_background_scroll_post" (func $set_background_scroll_post (param i32 i32 i32)))
+    (import "tile" "get_background_scroll_post" (func $get_background_scroll_post (param i32) (result i32 i32)))
+    (import "tile" "set_background_transformation_matrix" (func $set_background_transformation_matrix (param i32 i32 i32 i32 i32)))
+
+    (memory (export "memory") 1)
+    ;; Tile 1, a different color in each quadrant, so rotation and mirroring are visible
+    (data (i32.const 0) "\11\11\11\11\22\22\22\22\11\11\11\11\22\22\22\22\11\11\11\11\22\22\22\22\11\11\11\11\22\22\22\22\11\11\11\11\22\22\22\22\11\11\11\11\22\22\22\22\11\11\11\11\22\22\22\22\11\11\11\11\22\22\22\22\33\33\33\33\44\44\44\44\33\33\33\33\44\44\44\44\33\33\33\33\44\44\44\44\33\33\33\33\44\44\44\44\33\33\33\33\44\44\44\44\33\33\33\33\44\44\44\44\33\33\33\33\44\44\44\44\33\33\33\33\44\44\44\44")
+
+    (func (export "init") (local $x i32) (local $y i32)
+        (call $write_palette (i32.const 0) (i32.const 0xff4040))
+        (call $write_palette (i32.const 1) (i32.const 0x40ff40))
+        (call $write_palette (i32.const 2) (i32.const 0x4040ff))
+        (call $write_palette (i32.const 3) (i32.const 0xffff40))
+        (call $write_palette (i32.const 4) (i32.const 0x202020))
+        (call $set_background_palette (i32.const 4))
+        (call $write_tile (i32.const 1) (i32.const 0))
+
+        ;; BG0 is a checkerboard, BG1 is a sparse grid on top of it
+        (loop $rows
+            (local.set $x (i32.const 0))
+            (loop $columns
+                (if (i32.eqz (i32.and (i32.add (local.get $x) (local.get $y)) (i32.const 1)))
+                    (then (call $set_background_tile (i32.const 0) (local.get $x) (local.get $y) (i32.const 1))))
+                (if (i32.eqz (i32.and (i32.or (local.get $x) (local.get $y)) (i32.const 3)))
+                    (then (call $set_background_tile (i32.const 1) (local.get $x) (local.get $y) (i32.const 1))))
+                (local.set $x (i32.add (local.get $x) (i32.const 1)))
+                (br_if $columns (i32.lt_u (local.get $x) (i32.const 64))))
+            (local.set $y (i32.add (local.get $y) (i32.const 1)))
+            (br_if $rows (i32.lt_u (local.get $y) (i32.const 64))))
+
+        ;; Rotate BG0 by ~30 degrees around the center of the screen
+        (call $set_background_scroll_pre (i32.const 0) (i32.const -160) (i32.const -120))
+        (call $set_background_transformation_matrix (i32.const 0) (i32.const 222) (i32.const -128) (i32.const 128) (i32.const 222))
+        (call $set_background_scroll_post (i32.const 0) (i32.const 160) (i32.const 120))
+
+        ;; Mirror BG1 horizontally and scale it up 2x vertically
+        (call $set_background_transformation_matrix (i32.const 1) (i32.const -256) (i32.const 0) (i32.const 0) (i32.const 128)))
+
+    (func (export "update") (local $x i32) (local $y i32)
+        (call $get_background_scroll_post (i32.const 1))
+        (local.set $y)
+        (local.set $x)
+        (call $set_background_scroll_post (i32.const 1)
+            (i32.add (local.get $x) (i32.const 3))
+            (i32.sub (local.get $y) (i32.const 1))))
+
+    ;; A per-scanline wave on BG1
+    (func (export "v_blank") (param $y i32)
+        (call $set_background_scroll_pre (i32.const 1)
+            (i32.and (i32.shr_u (local.get $y) (i32.const 3)) (i32.const 3))
+            (i32.const 0))))
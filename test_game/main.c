@@ -4,6 +4,13 @@
 #include <debug.h>
 #include <tile.h>
 #include <sprite.h>
+#include <metadata.h>
+
+CART_METADATA(
+    "title=Test Game\n"
+    "api_version=1\n"
+    "gamepads=1\n"
+)
 
 const uint32_t PALETTE_GROUND[] = {
     0x796543,